@@ -0,0 +1,223 @@
+//! Key layouts of the column families ArangoDB uses.
+
+use std::fmt;
+
+use crate::key_decoder::{
+    fmt_hex_ascii, read_u64_be, ArangoDocumentDecoder, ArangoVPackDecoder, KeyDecoder,
+};
+
+/// The column families ArangoDB creates, in the order it creates them. The
+/// definitions family is RocksDB's "default" column family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArangoFamily {
+    /// The definitions, also RocksDB's default column family.
+    Definitions,
+    /// The documents.
+    Documents,
+    /// The primary index.
+    Primary,
+    /// The edge index.
+    Edge,
+    /// The persistent and other VelocyPack indexes.
+    VPack,
+    /// The geo index.
+    Geo,
+    /// The fulltext index.
+    Fulltext,
+    /// The replicated logs.
+    ReplicatedLogs,
+    /// The zkd index.
+    Zkd,
+    /// The zkd index with VelocyPack prefixes.
+    ZkdVPack,
+}
+
+const FAMILIES_BY_ID: [ArangoFamily; 10] = [
+    ArangoFamily::Definitions,
+    ArangoFamily::Documents,
+    ArangoFamily::Primary,
+    ArangoFamily::Edge,
+    ArangoFamily::VPack,
+    ArangoFamily::Geo,
+    ArangoFamily::Fulltext,
+    ArangoFamily::ReplicatedLogs,
+    ArangoFamily::Zkd,
+    ArangoFamily::ZkdVPack,
+];
+
+impl ArangoFamily {
+    /// Maps the RocksDB column family name ArangoDB uses to the family.
+    pub fn from_column_family_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(ArangoFamily::Definitions),
+            "Documents" => Some(ArangoFamily::Documents),
+            "PrimaryIndex" => Some(ArangoFamily::Primary),
+            "EdgeIndex" => Some(ArangoFamily::Edge),
+            "VPackIndex" => Some(ArangoFamily::VPack),
+            "GeoIndex" => Some(ArangoFamily::Geo),
+            "FulltextIndex" => Some(ArangoFamily::Fulltext),
+            "ReplicatedLogs" => Some(ArangoFamily::ReplicatedLogs),
+            "ZkdIndex" | "MdiIndex" => Some(ArangoFamily::Zkd),
+            "ZkdVPackIndex" | "MdiVPackIndex" => Some(ArangoFamily::ZkdVPack),
+            _ => None,
+        }
+    }
+
+    /// Fallback for manifests where the column family name is not known,
+    /// ArangoDB always creates its column families in the same order.
+    pub fn from_column_family_id(id: u32) -> Option<Self> {
+        FAMILIES_BY_ID.get(id as usize).copied()
+    }
+
+    /// The column family name ArangoDB uses.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArangoFamily::Definitions => "definitions",
+            ArangoFamily::Documents => "documents",
+            ArangoFamily::Primary => "primary",
+            ArangoFamily::Edge => "edge",
+            ArangoFamily::VPack => "vpack",
+            ArangoFamily::Geo => "geo",
+            ArangoFamily::Fulltext => "fulltext",
+            ArangoFamily::ReplicatedLogs => "replicated-logs",
+            ArangoFamily::Zkd => "zkd",
+            ArangoFamily::ZkdVPack => "zkd-vpack",
+        }
+    }
+}
+
+impl fmt::Display for ArangoFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// Names of the RocksDBEntryType prefixes of keys in the definitions family.
+fn definitions_entry_name(entry_type: u8) -> Option<&'static str> {
+    match entry_type {
+        b'0' => Some("Database"),
+        b'1' => Some("Collection"),
+        b'2' => Some("CounterValue"),
+        b'3' => Some("Document"),
+        b'4' => Some("PrimaryIndexValue"),
+        b'5' => Some("EdgeIndexValue"),
+        b'6' => Some("VPackIndexValue"),
+        b'7' => Some("UniqueVPackIndexValue"),
+        b'8' => Some("SettingsValue"),
+        b'9' => Some("ReplicationApplierConfig"),
+        b':' => Some("FulltextIndexValue"),
+        b';' => Some("LegacyGeoIndexValue"),
+        b'<' => Some("IndexEstimateValue"),
+        b'=' => Some("KeyGeneratorValue"),
+        b'>' => Some("View"),
+        b'?' => Some("GeoIndexValue"),
+        b'@' => Some("LogEntry"),
+        b'A' => Some("ReplicatedLog"),
+        b'B' => Some("ZkdIndexValue"),
+        b'C' => Some("UniqueZkdIndexValue"),
+        b'D' => Some("ReplicatedState"),
+        b'/' => Some("RevisionTreeValue"),
+        _ => None,
+    }
+}
+
+/// Decodes the keys of one ArangoDB column family. Apart from definitions
+/// all keys start with the big endian object id of the collection or index.
+pub struct ArangoFamilyDecoder {
+    /// The column family whose keys are decoded.
+    pub family: ArangoFamily,
+}
+
+impl ArangoFamilyDecoder {
+    fn fmt_definitions_key(&self, key: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(name) = key.first().and_then(|t| definitions_entry_name(*t)) else {
+            return fmt_hex_ascii(key, f);
+        };
+        write!(f, "{}", name)?;
+        let rest = &key[1..];
+        if rest.len().is_multiple_of(8) {
+            for id in rest.chunks(8) {
+                write!(f, " {}", read_u64_be(id).unwrap())?;
+            }
+            Ok(())
+        } else {
+            write!(f, " ")?;
+            fmt_hex_ascii(rest, f)
+        }
+    }
+
+    // Object id followed by a string (primary key or vertex id) and
+    // possibly a document id, or by a single number.
+    fn fmt_prefixed_key(&self, key: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(object_id) = read_u64_be(key) else {
+            return fmt_hex_ascii(key, f);
+        };
+        write!(f, "objectId {}", object_id)?;
+        let rest = &key[8..];
+        if rest.is_empty() {
+            return Ok(());
+        }
+        if rest.len() == 8 && self.family != ArangoFamily::Primary {
+            return write!(f, " {}", read_u64_be(rest).unwrap());
+        }
+        let text_len = rest
+            .iter()
+            .position(|b| !(b.is_ascii_graphic() || *b == b' '))
+            .unwrap_or(rest.len());
+        if text_len == 0 {
+            write!(f, " ")?;
+            return fmt_hex_ascii(rest, f);
+        }
+        write!(f, " {:?}", String::from_utf8_lossy(&rest[..text_len]))?;
+        let tail = &rest[text_len..];
+        // Edge index keys separate the vertex id from the document id
+        let tail = tail.strip_prefix(&[0xff]).unwrap_or(tail);
+        match read_u64_be(tail) {
+            Some(document_id) if tail.len() == 8 => write!(f, " documentId {}", document_id),
+            _ if tail.is_empty() => Ok(()),
+            _ => {
+                write!(f, " + ")?;
+                fmt_hex_ascii(tail, f)
+            }
+        }
+    }
+}
+
+impl KeyDecoder for ArangoFamilyDecoder {
+    fn name(&self) -> &'static str {
+        match self.family {
+            ArangoFamily::Definitions => "arangodb-definitions",
+            ArangoFamily::Documents => "arangodb-documents",
+            ArangoFamily::Primary => "arangodb-primary",
+            ArangoFamily::Edge => "arangodb-edge",
+            ArangoFamily::VPack => "arangodb-vpack",
+            ArangoFamily::Geo => "arangodb-geo",
+            ArangoFamily::Fulltext => "arangodb-fulltext",
+            ArangoFamily::ReplicatedLogs => "arangodb-replicated-logs",
+            ArangoFamily::Zkd => "arangodb-zkd",
+            ArangoFamily::ZkdVPack => "arangodb-zkd-vpack",
+        }
+    }
+
+    fn fmt_user_key(&self, key: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.family {
+            ArangoFamily::Definitions => self.fmt_definitions_key(key, f),
+            ArangoFamily::Documents => ArangoDocumentDecoder.fmt_user_key(key, f),
+            ArangoFamily::VPack | ArangoFamily::ZkdVPack => ArangoVPackDecoder.fmt_user_key(key, f),
+            _ => self.fmt_prefixed_key(key, f),
+        }
+    }
+
+    fn summarize_range(&self, smallest: &[u8], largest: &[u8]) -> Option<String> {
+        if self.family == ArangoFamily::Definitions {
+            return Some(self.family.name().to_string());
+        }
+        let first = read_u64_be(smallest)?;
+        let last = read_u64_be(largest)?;
+        if first == last {
+            Some(format!("{}, objectId {}", self.family, first))
+        } else {
+            Some(format!("{}, objectId {} .. {}", self.family, first, last))
+        }
+    }
+}
@@ -0,0 +1,546 @@
+//! Classifying records by the operation which wrote them, and finding
+//! deletion-only edits.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::edit::{record_column_family, FileMetaData, VersionEdit};
+use crate::key_decoder::{BytewiseDecoder, DisplayWithKeys, KeyDecoder};
+use crate::reader::Record;
+use crate::version::{ColumnFamilyLabel, ColumnFamilyNames};
+
+/// The operation which wrote a record, as far as it can be told from the
+/// edits alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RecordKind {
+    /// State written at the start of a new manifest.
+    Snapshot,
+    /// New L0 files and the log number advanced.
+    Flush,
+    /// Files deleted and others added.
+    Compaction,
+    /// Files deleted and re-added on another level.
+    TrivialMove,
+    /// Files deleted, none added.
+    DeletionOnly,
+    /// New files without a log number advance.
+    Ingestion,
+    /// Column family created.
+    ColumnFamilyAdd,
+    /// Column family dropped.
+    ColumnFamilyDrop,
+    /// Only WAL additions and deletions.
+    WalTracking,
+    /// Bookkeeping like log or file numbers only.
+    Other,
+}
+
+/// All kinds, in the order reports list them.
+pub const RECORD_KINDS: &[RecordKind] = &[
+    RecordKind::Snapshot,
+    RecordKind::Flush,
+    RecordKind::Compaction,
+    RecordKind::TrivialMove,
+    RecordKind::DeletionOnly,
+    RecordKind::Ingestion,
+    RecordKind::ColumnFamilyAdd,
+    RecordKind::ColumnFamilyDrop,
+    RecordKind::WalTracking,
+    RecordKind::Other,
+];
+
+impl fmt::Display for RecordKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// A record with its kind and the files it deletes and adds.
+#[derive(Debug, Clone)]
+pub struct ClassifiedRecord {
+    /// Index of the record in the manifest.
+    pub index: usize,
+    /// Start of the record in the file.
+    pub offset: u64,
+    /// What wrote the record.
+    pub kind: RecordKind,
+    /// Column family the record belongs to.
+    pub column_family: u32,
+    /// Name of the column family, if the manifest told it so far.
+    pub column_family_name: Option<String>,
+    /// The LogNumber edit, if any.
+    pub log_number: Option<u64>,
+    /// The NextFileNumber edit, if any.
+    pub next_file_number: Option<u64>,
+    /// The LastSequence edit, if any.
+    pub last_sequence: Option<u64>,
+    /// Level and number of every deleted file.
+    pub deleted_files: Vec<(u32, u64)>,
+    /// Every added file, moved files included.
+    pub new_files: Vec<FileMetaData>,
+    /// Number, old level and new level of every trivially moved file.
+    pub moved_files: Vec<(u64, u32, u32)>,
+}
+
+impl ClassifiedRecord {
+    /// Files which were rewritten, i.e. deleted and not re-added.
+    pub fn input_files(&self) -> impl Iterator<Item = &(u32, u64)> {
+        self.deleted_files
+            .iter()
+            .filter(|(_, n)| !self.moved_files.iter().any(|(m, _, _)| m == n))
+    }
+
+    /// Files which were written, i.e. added and not just moved.
+    pub fn output_files(&self) -> impl Iterator<Item = &FileMetaData> {
+        self.new_files.iter().filter(|meta| {
+            !self
+                .moved_files
+                .iter()
+                .any(|(m, _, _)| *m == meta.file_number)
+        })
+    }
+}
+
+impl fmt::Display for ClassifiedRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with_keys(f, &BytewiseDecoder)
+    }
+}
+
+impl DisplayWithKeys for ClassifiedRecord {
+    fn fmt_with_keys(&self, f: &mut fmt::Formatter<'_>, keys: &dyn KeyDecoder) -> fmt::Result {
+        writeln!(
+            f,
+            "{} at position {} (offset {:x}) {{",
+            self.kind, self.index, self.offset
+        )?;
+        let label = ColumnFamilyLabel {
+            id: self.column_family,
+            name: self.column_family_name.as_deref(),
+        };
+        writeln!(f, "  ColumnFamily: {}", label)?;
+        if let Some(log_number) = self.log_number {
+            writeln!(f, "  LogNumber: {}", log_number)?;
+        }
+        if let Some(next_file_number) = self.next_file_number {
+            writeln!(f, "  NextFileNumber: {}", next_file_number)?;
+        }
+        if let Some(last_sequence) = self.last_sequence {
+            writeln!(f, "  LastSequence: {}", last_sequence)?;
+        }
+        if !self.deleted_files.is_empty() {
+            writeln!(f, "  Deleted files:")?;
+            for (level, file) in &self.deleted_files {
+                writeln!(f, "    Level {}: File {}", level, file)?;
+            }
+        }
+        if !self.new_files.is_empty() {
+            writeln!(f, "  New files:")?;
+            for file in &self.new_files {
+                writeln!(f, "    {}", file.labeled(keys, label))?;
+            }
+        }
+        if !self.moved_files.is_empty() {
+            writeln!(f, "  Moved files:")?;
+            for (file, from, to) in &self.moved_files {
+                writeln!(f, "    File {}: level {} -> {}", file, from, to)?;
+            }
+        }
+        write!(f, "}}")
+    }
+}
+
+/// Classifies records one after the other. The records before the first
+/// one with a LastSequence are the snapshot of the state RocksDB writes
+/// when it starts a new manifest, a new database writes its initial state
+/// with the comparator instead.
+#[derive(Default)]
+pub struct RecordClassifier {
+    after_snapshot: bool,
+    names: ColumnFamilyNames,
+}
+
+impl RecordClassifier {
+    /// Classifies the next record.
+    pub fn classify(&mut self, record: &Record) -> ClassifiedRecord {
+        self.names.apply(record);
+        let column_family = record_column_family(&record.edits);
+        let mut classified = ClassifiedRecord {
+            index: record.index,
+            offset: record.offset,
+            kind: RecordKind::Other,
+            column_family,
+            column_family_name: self.names.name(column_family).map(str::to_string),
+            log_number: None,
+            next_file_number: None,
+            last_sequence: None,
+            deleted_files: Vec::new(),
+            new_files: Vec::new(),
+            moved_files: Vec::new(),
+        };
+        let mut cf_add = false;
+        let mut cf_drop = false;
+        let mut comparator = false;
+        let mut only_wals = true;
+        // Blob files count as added files, like for deletion-only edits
+        let mut adds_blob_files = false;
+        for e in &record.edits {
+            match e {
+                VersionEdit::LogNumber(num) => classified.log_number = Some(*num),
+                VersionEdit::NextFileNumber(num) => classified.next_file_number = Some(*num),
+                VersionEdit::LastSequence(seq) => classified.last_sequence = Some(*seq),
+                VersionEdit::DeletedFile(level, file_number) => {
+                    classified.deleted_files.push((*level, *file_number))
+                }
+                VersionEdit::NewFile4(meta) => classified.new_files.push(meta.clone()),
+                VersionEdit::BlobFileAddition(_) => adds_blob_files = true,
+                VersionEdit::ColumnFamilyAdd(_) => cf_add = true,
+                VersionEdit::ColumnFamilyDrop => cf_drop = true,
+                VersionEdit::Comparator(_) => comparator = true,
+                _ => {}
+            }
+            if !matches!(
+                e,
+                VersionEdit::WalAddition(..) | VersionEdit::WalDeletion(_)
+            ) {
+                only_wals = false;
+            }
+        }
+        for (level, file_number) in &classified.deleted_files {
+            if let Some(meta) = classified
+                .new_files
+                .iter()
+                .find(|meta| meta.file_number == *file_number && meta.level != *level)
+            {
+                classified
+                    .moved_files
+                    .push((*file_number, *level, meta.level));
+            }
+        }
+
+        let deletes = !classified.deleted_files.is_empty();
+        let adds = !classified.new_files.is_empty() || adds_blob_files;
+        let moves_only = !adds_blob_files
+            && deletes
+            && classified.moved_files.len() == classified.deleted_files.len()
+            && classified.moved_files.len() == classified.new_files.len();
+        let in_snapshot = !self.after_snapshot && classified.last_sequence.is_none();
+        classified.kind = if in_snapshot || (comparator && !cf_add) {
+            RecordKind::Snapshot
+        } else if cf_drop {
+            RecordKind::ColumnFamilyDrop
+        } else if cf_add {
+            RecordKind::ColumnFamilyAdd
+        } else if moves_only {
+            RecordKind::TrivialMove
+        } else if deletes && adds {
+            RecordKind::Compaction
+        } else if deletes {
+            RecordKind::DeletionOnly
+        } else if adds && classified.log_number.is_some() {
+            if classified.new_files.iter().all(|meta| meta.level == 0) {
+                RecordKind::Flush
+            } else {
+                RecordKind::Other
+            }
+        } else if adds {
+            RecordKind::Ingestion
+        } else if only_wals && !record.edits.is_empty() {
+            RecordKind::WalTracking
+        } else {
+            RecordKind::Other
+        };
+        if classified.last_sequence.is_some() {
+            self.after_snapshot = true;
+        }
+        classified
+    }
+}
+
+/// Classifies all records.
+pub fn classify_records(records: &[Record]) -> Vec<ClassifiedRecord> {
+    let mut classifier = RecordClassifier::default();
+    records.iter().map(|r| classifier.classify(r)).collect()
+}
+
+/// A file removed by a deletion-only edit, with its metadata if the file
+/// was added earlier in the manifest.
+#[derive(Debug, Clone)]
+pub struct RemovedFile {
+    /// Level the file was deleted from.
+    pub level: u32,
+    /// Number of the deleted file.
+    pub file_number: u64,
+    /// The file as it was added, None if it was not added in this manifest.
+    pub meta: Option<FileMetaData>,
+}
+
+impl RemovedFile {
+    /// Size of the file, 0 if it is not known.
+    pub fn file_size(&self) -> u64 {
+        self.meta.as_ref().map_or(0, |meta| meta.file_size)
+    }
+}
+
+/// A record which removes files without adding any, e.g. from
+/// DeleteFilesInRange, FIFO or TTL compaction, or dropping files which
+/// only contained obsolete data.
+#[derive(Debug, Clone)]
+pub struct DeletionOnlyEdit {
+    /// Of the record.
+    pub index: usize,
+    /// Start of the record in the file.
+    pub offset: u64,
+    /// Column family the record belongs to.
+    pub column_family: u32,
+    /// The files the record deleted.
+    pub removed: Vec<RemovedFile>,
+}
+
+impl DeletionOnlyEdit {
+    /// Bytes removed, as far as the sizes are known.
+    pub fn total_size(&self) -> u64 {
+        self.removed.iter().map(RemovedFile::file_size).sum()
+    }
+
+    /// Size of the largest removed file.
+    pub fn largest_file_size(&self) -> u64 {
+        self.removed
+            .iter()
+            .map(RemovedFile::file_size)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Size limits for the deletion-only edits worth reporting.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeletionThresholds {
+    /// Every removed file at most this big.
+    pub max_file_size: Option<u64>,
+    /// At least this many bytes removed.
+    pub min_total_size: Option<u64>,
+}
+
+impl DeletionThresholds {
+    /// Tells if the edit is worth reporting.
+    pub fn accepts(&self, edit: &DeletionOnlyEdit) -> bool {
+        self.max_file_size
+            .is_none_or(|max| edit.largest_file_size() <= max)
+            && self
+                .min_total_size
+                .is_none_or(|min| edit.total_size() >= min)
+    }
+}
+
+/// Finds the records which delete files without adding any. The removed
+/// files are looked up in the files added by earlier records, so sizes and
+/// key ranges are those the files had when they were deleted.
+///
+/// ```
+/// use manifest_dumper::compaction::find_deletion_only_edits;
+/// use manifest_dumper::{FileMetaData, Record, VersionEdit};
+///
+/// let record = |index, edits| Record { index, offset: 0, length: 0, edits };
+/// let file = |file_number, file_size| {
+///     VersionEdit::NewFile4(FileMetaData { file_number, file_size, ..Default::default() })
+/// };
+/// let records = vec![
+///     record(0, vec![file(5, 100), file(6, 3000)]),
+///     // A compaction, it adds a file
+///     record(1, vec![VersionEdit::DeletedFile(0, 6), file(7, 2900)]),
+///     record(2, vec![VersionEdit::DeletedFile(0, 5), VersionEdit::ColumnFamily(10)]),
+/// ];
+/// let edits = find_deletion_only_edits(&records);
+/// assert_eq!(edits.len(), 1);
+/// assert_eq!(edits[0].index, 2);
+/// assert_eq!(edits[0].column_family, 10);
+/// assert_eq!(edits[0].total_size(), 100);
+/// ```
+pub fn find_deletion_only_edits(records: &[Record]) -> Vec<DeletionOnlyEdit> {
+    let mut files: HashMap<u64, FileMetaData> = HashMap::new();
+    let mut edits = Vec::new();
+    for record in records {
+        let mut removed = Vec::new();
+        let mut adds_files = false;
+        for e in &record.edits {
+            match e {
+                VersionEdit::DeletedFile(level, file_number) => removed.push(RemovedFile {
+                    level: *level,
+                    file_number: *file_number,
+                    meta: files.get(file_number).cloned(),
+                }),
+                VersionEdit::NewFile4(_) | VersionEdit::BlobFileAddition(_) => adds_files = true,
+                _ => {}
+            }
+        }
+        for e in &record.edits {
+            if let VersionEdit::NewFile4(meta) = e {
+                files.insert(meta.file_number, meta.clone());
+            }
+        }
+        if !removed.is_empty() && !adds_files {
+            edits.push(DeletionOnlyEdit {
+                index: record.index,
+                offset: record.offset,
+                column_family: record_column_family(&record.edits),
+                removed,
+            });
+        }
+    }
+    edits
+}
+
+/// Groups deletion-only edits by the column family they belong to, keeping
+/// the order of the edits within each column family.
+pub fn group_by_column_family(
+    edits: impl IntoIterator<Item = DeletionOnlyEdit>,
+) -> BTreeMap<u32, Vec<DeletionOnlyEdit>> {
+    let mut by_column_family: BTreeMap<u32, Vec<DeletionOnlyEdit>> = BTreeMap::new();
+    for edit in edits {
+        by_column_family
+            .entry(edit.column_family)
+            .or_default()
+            .push(edit);
+    }
+    by_column_family
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::BlobFileAddition;
+    use crate::reader::record;
+
+    fn file(file_number: u64, file_size: u64) -> VersionEdit {
+        VersionEdit::NewFile4(FileMetaData {
+            file_number,
+            file_size,
+            ..Default::default()
+        })
+    }
+
+    fn deletion(index: usize, sizes: &[u64]) -> DeletionOnlyEdit {
+        DeletionOnlyEdit {
+            index,
+            offset: 0,
+            column_family: 0,
+            removed: sizes
+                .iter()
+                .enumerate()
+                .map(|(i, size)| RemovedFile {
+                    level: 0,
+                    file_number: i as u64,
+                    meta: Some(FileMetaData {
+                        file_size: *size,
+                        ..Default::default()
+                    }),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn no_thresholds_accept_everything() {
+        let thresholds = DeletionThresholds::default();
+        assert!(thresholds.accepts(&deletion(0, &[])));
+        assert!(thresholds.accepts(&deletion(0, &[1 << 40])));
+    }
+
+    #[test]
+    fn max_file_size_applies_to_every_file() {
+        let thresholds = DeletionThresholds {
+            max_file_size: Some(2000),
+            min_total_size: None,
+        };
+        assert!(thresholds.accepts(&deletion(0, &[1000, 2000])));
+        assert!(!thresholds.accepts(&deletion(0, &[1000, 2001])));
+    }
+
+    #[test]
+    fn min_total_size_applies_to_the_sum() {
+        let thresholds = DeletionThresholds {
+            max_file_size: None,
+            min_total_size: Some(3000),
+        };
+        assert!(thresholds.accepts(&deletion(0, &[1000, 2000])));
+        assert!(!thresholds.accepts(&deletion(0, &[1000, 1999])));
+    }
+
+    #[test]
+    fn both_thresholds_must_hold() {
+        let thresholds = DeletionThresholds {
+            max_file_size: Some(2000),
+            min_total_size: Some(3000),
+        };
+        assert!(thresholds.accepts(&deletion(0, &[1500, 1500])));
+        assert!(!thresholds.accepts(&deletion(0, &[1000, 2500])));
+        assert!(!thresholds.accepts(&deletion(0, &[1000, 1000])));
+    }
+
+    #[test]
+    fn edits_are_grouped_per_column_family() {
+        let records = vec![
+            record(0, vec![file(5, 100), file(6, 200), file(7, 300)]),
+            record(
+                1,
+                vec![VersionEdit::ColumnFamily(2), VersionEdit::DeletedFile(1, 5)],
+            ),
+            record(2, vec![VersionEdit::DeletedFile(1, 6)]),
+            record(
+                3,
+                vec![VersionEdit::ColumnFamily(2), VersionEdit::DeletedFile(1, 7)],
+            ),
+        ];
+        let grouped = group_by_column_family(find_deletion_only_edits(&records));
+        let indices: Vec<(u32, Vec<usize>)> = grouped
+            .iter()
+            .map(|(id, edits)| (*id, edits.iter().map(|e| e.index).collect()))
+            .collect();
+        assert_eq!(indices, vec![(0, vec![2]), (2, vec![1, 3])]);
+    }
+
+    #[test]
+    fn deletions_of_unknown_files_have_no_size() {
+        let records = vec![record(
+            0,
+            vec![
+                VersionEdit::DeletedFile(3, 42),
+                VersionEdit::DeletedFile(3, 43),
+            ],
+        )];
+        let edits = find_deletion_only_edits(&records);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].removed.len(), 2);
+        assert!(edits[0].removed.iter().all(|r| r.meta.is_none()));
+        assert_eq!(edits[0].removed[0].level, 3);
+        assert_eq!(edits[0].total_size(), 0);
+        assert_eq!(edits[0].largest_file_size(), 0);
+    }
+
+    #[test]
+    fn edits_adding_blob_files_are_not_deletion_only() {
+        let blob = VersionEdit::BlobFileAddition(BlobFileAddition {
+            blob_file_number: 9,
+            total_blob_count: 1,
+            total_blob_bytes: 10,
+            checksum_method: String::new(),
+            checksum_value: Vec::new(),
+        });
+        let records = vec![
+            // Ends the snapshot, so that the records below are classified
+            record(
+                0,
+                vec![file(5, 100), file(6, 200), VersionEdit::LastSequence(1)],
+            ),
+            record(1, vec![VersionEdit::DeletedFile(0, 5), blob]),
+            record(2, vec![VersionEdit::DeletedFile(0, 6)]),
+        ];
+        let edits = find_deletion_only_edits(&records);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].index, 2);
+        let kinds: Vec<RecordKind> = classify_records(&records).iter().map(|r| r.kind).collect();
+        assert_eq!(kinds[1], RecordKind::Compaction);
+        assert_eq!(kinds[2], RecordKind::DeletionOnly);
+    }
+}
@@ -0,0 +1,260 @@
+//! The files of a database directory: finding its manifests, and
+//! checking the files against the live files of a replayed manifest.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::version::{LiveFiles, VersionSet};
+
+// Blob files have a header and a footer around the blob records, which
+// total_blob_bytes counts.
+const BLOB_LOG_HEADER_SIZE: u64 = 30;
+const BLOB_LOG_FOOTER_SIZE: u64 = 32;
+
+/// The kinds of numbered files a database directory holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DbFileKind {
+    /// An SST file, `NNNNNN.sst`.
+    Table,
+    /// A blob file, `NNNNNN.blob`.
+    Blob,
+    /// A write-ahead log, `NNNNNN.log`.
+    Wal,
+}
+
+/// A numbered file of a database directory, with its size on disk or as
+/// the manifest expects it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DbFile {
+    /// Which kind of file.
+    pub kind: DbFileKind,
+    /// The file number.
+    pub number: u64,
+    /// None for WALs not synced yet.
+    pub size: Option<u64>,
+}
+
+/// Parses names like `000123.sst`, `000123.blob` and `000123.log`.
+pub fn parse_file_name(name: &str) -> Option<(DbFileKind, u64)> {
+    let (number, extension) = name.split_once('.')?;
+    let kind = match extension {
+        "sst" => DbFileKind::Table,
+        "blob" => DbFileKind::Blob,
+        "log" => DbFileKind::Wal,
+        _ => return None,
+    };
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((kind, number.parse().ok()?))
+}
+
+impl fmt::Display for DbFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let extension = match self.kind {
+            DbFileKind::Table => "sst",
+            DbFileKind::Blob => "blob",
+            DbFileKind::Wal => "log",
+        };
+        write!(f, "{:06}.{}", self.number, extension)
+    }
+}
+
+/// A difference between the manifest and the directory.
+#[derive(Debug, Clone)]
+pub enum Discrepancy {
+    /// Live in the manifest, not on disk.
+    Missing(DbFile),
+    /// On disk, not referenced by the manifest.
+    Orphaned(DbFile),
+    /// On disk with another size than the manifest expects.
+    SizeMismatch {
+        /// The file as the manifest expects it.
+        expected: DbFile,
+        /// Size on disk.
+        actual: u64,
+    },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Discrepancy::Missing(file) => match file.size {
+                Some(size) => write!(f, "Missing: {} ({} bytes)", file, size),
+                None => write!(f, "Missing: {}", file),
+            },
+            Discrepancy::Orphaned(file) => {
+                write!(f, "Orphaned: {} ({} bytes)", file, file.size.unwrap_or(0))
+            }
+            Discrepancy::SizeMismatch { expected, actual } if expected.kind == DbFileKind::Wal => {
+                write!(
+                    f,
+                    "Size mismatch: {} synced {} bytes, but has {} bytes on disk",
+                    expected,
+                    expected.size.unwrap_or(0),
+                    actual
+                )
+            }
+            Discrepancy::SizeMismatch { expected, actual } => write!(
+                f,
+                "Size mismatch: {} has {} bytes in the manifest, {} bytes on disk",
+                expected,
+                expected.size.unwrap_or(0),
+                actual
+            ),
+        }
+    }
+}
+
+/// The numbered files in a directory, other files are ignored.
+pub fn list_directory(dir: &Path) -> io::Result<Vec<DbFile>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let Some((kind, number)) = entry.file_name().to_str().and_then(parse_file_name) else {
+            continue;
+        };
+        files.push(DbFile {
+            kind,
+            number,
+            size: Some(entry.metadata()?.len()),
+        });
+    }
+    files.sort_by_key(|f| (f.kind, f.number));
+    Ok(files)
+}
+
+/// The files the manifest says are alive and in the directory: SST files
+/// of path 0, blob files which are neither all garbage nor dropped with
+/// their column family, and tracked WALs.
+/// Files on other `db_paths` cannot be checked.
+pub fn expected_files(live: &LiveFiles) -> Vec<DbFile> {
+    let mut files: Vec<DbFile> = live
+        .files
+        .values()
+        .filter(|meta| !meta.deleted && meta.path_id == 0)
+        .map(|meta| DbFile {
+            kind: DbFileKind::Table,
+            number: meta.file_number,
+            size: Some(meta.file_size),
+        })
+        .chain(
+            live.blob_files
+                .values()
+                .filter(|blob| !blob.is_obsolete())
+                .map(|blob| DbFile {
+                    kind: DbFileKind::Blob,
+                    number: blob.addition.blob_file_number,
+                    size: Some(
+                        BLOB_LOG_HEADER_SIZE
+                            + blob.addition.total_blob_bytes
+                            + BLOB_LOG_FOOTER_SIZE,
+                    ),
+                }),
+        )
+        .chain(live.wals.iter().map(|(number, synced_size)| DbFile {
+            kind: DbFileKind::Wal,
+            number: *number,
+            size: *synced_size,
+        }))
+        .collect();
+    files.sort_by_key(|f| (f.kind, f.number));
+    files
+}
+
+/// Compares the live files with those in the directory. A WAL is only
+/// orphaned if it is older than the log numbers of all live column
+/// families and than MinLogNumberToKeep, which keeps the WALs of prepared
+/// transactions, since the current WAL is usually not tracked, and it may
+/// have grown beyond its synced size.
+pub fn check_directory(
+    dir: &Path,
+    live: &LiveFiles,
+    versions: &VersionSet,
+) -> io::Result<Vec<Discrepancy>> {
+    let found = list_directory(dir)?;
+    let expected = expected_files(live);
+    let min_log_number = versions
+        .live_column_families()
+        .map(|cf| cf.log_number)
+        .chain(Some(versions.min_log_number_to_keep).filter(|n| *n != 0))
+        .min()
+        .unwrap_or(0);
+    let mut discrepancies = Vec::new();
+    for file in &expected {
+        let on_disk = found
+            .iter()
+            .find(|f| f.kind == file.kind && f.number == file.number);
+        match (on_disk.and_then(|f| f.size), file.size) {
+            (None, _) => discrepancies.push(Discrepancy::Missing(*file)),
+            (Some(actual), Some(size)) => {
+                let wrong = match file.kind {
+                    DbFileKind::Wal => actual < size,
+                    _ => actual != size,
+                };
+                if wrong {
+                    discrepancies.push(Discrepancy::SizeMismatch {
+                        expected: *file,
+                        actual,
+                    });
+                }
+            }
+            (Some(_), None) => {}
+        }
+    }
+    for file in &found {
+        let referenced = expected
+            .iter()
+            .any(|f| f.kind == file.kind && f.number == file.number);
+        let needed = file.kind == DbFileKind::Wal && file.number >= min_log_number;
+        if !referenced && !needed {
+            discrepancies.push(Discrepancy::Orphaned(*file));
+        }
+    }
+    Ok(discrepancies)
+}
+
+/// The number of a `MANIFEST-NNNNNN` file name.
+pub fn parse_manifest_name(name: &str) -> Option<u64> {
+    let number = name.strip_prefix("MANIFEST-")?;
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// The active manifest of a database directory, as named by its CURRENT
+/// file. RocksDB writes CURRENT with a trailing newline, a file without
+/// one was not completely written.
+pub fn current_manifest(dir: &Path) -> io::Result<PathBuf> {
+    let current = fs::read_to_string(dir.join("CURRENT"))
+        .map_err(|e| io::Error::new(e.kind(), format!("CURRENT in {}: {}", dir.display(), e)))?;
+    let invalid = |message: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("CURRENT in {}: {}", dir.display(), message),
+        )
+    };
+    let name = current
+        .strip_suffix('\n')
+        .ok_or_else(|| invalid("does not end with a newline"))?;
+    if parse_manifest_name(name).is_none() {
+        return Err(invalid(&format!("{:?} is not a manifest file name", name)));
+    }
+    Ok(dir.join(name))
+}
+
+/// All manifest files of a directory, ordered by number.
+pub fn manifest_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut manifests = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if let Some(number) = entry.file_name().to_str().and_then(parse_manifest_name) {
+            manifests.push((number, entry.path()));
+        }
+    }
+    manifests.sort();
+    Ok(manifests.into_iter().map(|(_, path)| path).collect())
+}
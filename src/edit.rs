@@ -0,0 +1,816 @@
+//! Decoded contents of manifest records.
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use chrono::{TimeZone, Utc};
+use std::fmt;
+
+use crate::key_decoder::{BytewiseDecoder, DisplayWithKeys, KeyDecoder, UserKey};
+use crate::version::ColumnFamilyLabel;
+
+/// Largest sequence number, used in range tombstone sentinel keys.
+pub const MAX_SEQUENCE_NUMBER: u64 = (1 << 56) - 1;
+
+/// The type of an entry, stored in the lowest byte of the internal key
+/// trailer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    /// `kTypeDeletion`.
+    Deletion,
+    /// `kTypeValue`.
+    Value,
+    /// `kTypeMerge`.
+    Merge,
+    /// `kTypeLogData`.
+    LogData,
+    /// `kTypeColumnFamilyDeletion`.
+    ColumnFamilyDeletion,
+    /// `kTypeColumnFamilyValue`.
+    ColumnFamilyValue,
+    /// `kTypeColumnFamilyMerge`.
+    ColumnFamilyMerge,
+    /// `kTypeSingleDeletion`.
+    SingleDeletion,
+    /// `kTypeColumnFamilySingleDeletion`.
+    ColumnFamilySingleDeletion,
+    /// `kTypeBeginPrepareXID`.
+    BeginPrepareXID,
+    /// `kTypeEndPrepareXID`.
+    EndPrepareXID,
+    /// `kTypeCommitXID`.
+    CommitXID,
+    /// `kTypeRollbackXID`.
+    RollbackXID,
+    /// `kTypeNoop`.
+    Noop,
+    /// `kTypeColumnFamilyRangeDeletion`.
+    ColumnFamilyRangeDeletion,
+    /// `kTypeRangeDeletion`.
+    RangeDeletion,
+    /// `kTypeColumnFamilyBlobIndex`.
+    ColumnFamilyBlobIndex,
+    /// `kTypeBlobIndex`.
+    BlobIndex,
+    /// `kTypeBeginPersistedPrepareXID`.
+    BeginPersistedPrepareXID,
+    /// `kTypeBeginUnprepareXID`.
+    BeginUnprepareXID,
+    /// `kTypeDeletionWithTimestamp`.
+    DeletionWithTimestamp,
+    /// `kTypeCommitXIDAndTimestamp`.
+    CommitXIDAndTimestamp,
+    /// `kTypeWideColumnEntity`.
+    WideColumnEntity,
+    /// `kTypeColumnFamilyWideColumnEntity`.
+    ColumnFamilyWideColumnEntity,
+    /// `kTypeValuePreferredSeqno`.
+    ValuePreferredSeqno,
+    /// `kTypeColumnFamilyValuePreferredSeqno`.
+    ColumnFamilyValuePreferredSeqno,
+    /// Used for seeking, not for stored entries.
+    MaxValue,
+    /// A type this tool does not know.
+    Unknown(u8),
+    /// Key shorter than the 8-byte trailer.
+    Truncated,
+}
+
+impl From<u8> for ValueType {
+    fn from(value: u8) -> Self {
+        match value {
+            0x00 => ValueType::Deletion,
+            0x01 => ValueType::Value,
+            0x02 => ValueType::Merge,
+            0x03 => ValueType::LogData,
+            0x04 => ValueType::ColumnFamilyDeletion,
+            0x05 => ValueType::ColumnFamilyValue,
+            0x06 => ValueType::ColumnFamilyMerge,
+            0x07 => ValueType::SingleDeletion,
+            0x08 => ValueType::ColumnFamilySingleDeletion,
+            0x09 => ValueType::BeginPrepareXID,
+            0x0a => ValueType::EndPrepareXID,
+            0x0b => ValueType::CommitXID,
+            0x0c => ValueType::RollbackXID,
+            0x0d => ValueType::Noop,
+            0x0e => ValueType::ColumnFamilyRangeDeletion,
+            0x0f => ValueType::RangeDeletion,
+            0x10 => ValueType::ColumnFamilyBlobIndex,
+            0x11 => ValueType::BlobIndex,
+            0x12 => ValueType::BeginPersistedPrepareXID,
+            0x13 => ValueType::BeginUnprepareXID,
+            0x14 => ValueType::DeletionWithTimestamp,
+            0x15 => ValueType::CommitXIDAndTimestamp,
+            0x16 => ValueType::WideColumnEntity,
+            0x17 => ValueType::ColumnFamilyWideColumnEntity,
+            0x18 => ValueType::ValuePreferredSeqno,
+            0x19 => ValueType::ColumnFamilyValuePreferredSeqno,
+            0x7f => ValueType::MaxValue,
+            other => ValueType::Unknown(other),
+        }
+    }
+}
+
+/// Displays unix seconds as `YYYY-MM-DD HH:MM:SS UTC`, or as the plain
+/// number if they are out of range for a date. The alternate form `{:#}`
+/// leaves out the ` UTC`, for tables.
+#[derive(Debug, Clone, Copy)]
+pub struct UnixTime(pub u64);
+
+impl fmt::Display for UnixTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dt = i64::try_from(self.0)
+            .ok()
+            .and_then(|secs| Utc.timestamp_opt(secs, 0).single());
+        match dt {
+            Some(dt) if f.alternate() => write!(f, "{}", dt.format("%Y-%m-%d %H:%M:%S")),
+            Some(dt) => write!(f, "{}", dt.format("%Y-%m-%d %H:%M:%S UTC")),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueType::Unknown(value) => write!(f, "Unknown(0x{:02x})", value),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// A key as stored in SST files: the user key followed by a trailer with
+/// sequence number and value type.
+#[derive(Debug, Clone)]
+pub struct InternalKey {
+    /// The key as the application wrote it.
+    pub user_key: Vec<u8>,
+    /// Only 56 bits are used.
+    pub sequence: u64,
+    /// Type of the entry.
+    pub value_type: ValueType,
+}
+
+impl InternalKey {
+    /// Splits the raw key into user key and the trailer, which is a fixed
+    /// 64-bit little endian (sequence << 8 | type).
+    pub fn decode(data: Vec<u8>) -> Self {
+        if data.len() < 8 {
+            return InternalKey {
+                user_key: data,
+                sequence: 0,
+                value_type: ValueType::Truncated,
+            };
+        }
+        let split = data.len() - 8;
+        let trailer = (&data[split..]).read_u64::<LittleEndian>().unwrap();
+        let mut user_key = data;
+        user_key.truncate(split);
+        InternalKey {
+            user_key,
+            sequence: trailer >> 8,
+            value_type: ValueType::from((trailer & 0xff) as u8),
+        }
+    }
+
+    /// A key without user key, e.g. for missing fields.
+    pub fn empty() -> Self {
+        InternalKey {
+            user_key: Vec::new(),
+            sequence: 0,
+            value_type: ValueType::Truncated,
+        }
+    }
+
+    /// The largest key of a file which ends with a range tombstone.
+    pub fn is_range_tombstone_sentinel(&self) -> bool {
+        self.sequence == MAX_SEQUENCE_NUMBER && self.value_type == ValueType::RangeDeletion
+    }
+
+    /// Displays the sequence number and type.
+    pub fn trailer(&self) -> InternalKeyTrailer<'_> {
+        InternalKeyTrailer(self)
+    }
+}
+
+/// Prints the sequence number and type of an internal key.
+pub struct InternalKeyTrailer<'a>(&'a InternalKey);
+
+impl fmt::Display for InternalKeyTrailer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = self.0;
+        if key.value_type == ValueType::Truncated {
+            return write!(f, "none");
+        }
+        if key.sequence == MAX_SEQUENCE_NUMBER {
+            write!(f, "seq max type {}", key.value_type)?;
+        } else {
+            write!(f, "seq {} type {}", key.sequence, key.value_type)?;
+        }
+        if key.is_range_tombstone_sentinel() {
+            write!(f, " (range tombstone sentinel)")?;
+        }
+        Ok(())
+    }
+}
+
+impl DisplayWithKeys for InternalKey {
+    fn fmt_with_keys(&self, f: &mut fmt::Formatter<'_>, keys: &dyn KeyDecoder) -> fmt::Result {
+        write!(f, "{} {}", UserKey(&self.user_key, keys), self.trailer())
+    }
+}
+
+impl fmt::Display for InternalKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with_keys(f, &BytewiseDecoder)
+    }
+}
+
+/// The encoding a file addition was read from. Only kNewFile4 is written by
+/// current RocksDB versions, the others are from manifests which have not
+/// been rewritten for a long time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NewFileFormat {
+    /// `kNewFile`, the original format.
+    NewFile,
+    /// `kNewFile2`, with sequence numbers.
+    NewFile2,
+    /// `kNewFile3`, with a path id.
+    NewFile3,
+    /// `kNewFile4`, with custom fields.
+    NewFile4,
+}
+
+impl fmt::Display for NewFileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            NewFileFormat::NewFile => "NewFile",
+            NewFileFormat::NewFile2 => "NewFile2",
+            NewFileFormat::NewFile3 => "NewFile3",
+            NewFileFormat::NewFile4 => "NewFile4",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// An SST file added to a level, decoded from any of the NewFile formats.
+#[derive(Debug, Clone)]
+pub struct FileMetaData {
+    /// The encoding the addition was read from.
+    pub format: NewFileFormat,
+    /// From the ColumnFamily edit of the same record.
+    pub column_family: u32,
+    /// Level the file was added to.
+    pub level: u32,
+    /// Number of the file, `NNNNNN.sst`.
+    pub file_number: u64,
+    /// Size of the file in bytes.
+    pub file_size: u64,
+    /// Smallest internal key in the file.
+    pub smallest_key: InternalKey,
+    /// Largest internal key in the file.
+    pub largest_key: InternalKey,
+    /// Smallest sequence number in the file.
+    pub smallest_seqno: u64,
+    /// Largest sequence number in the file.
+    pub largest_seqno: u64,
+    // Custom fields
+    /// Index into the `db_paths` of the database.
+    pub path_id: u32,
+    /// Marked for compaction, e.g. by a compaction filter.
+    pub needs_compaction: bool,
+    /// Oldest WAL needed to recover the file's data, if tracked.
+    pub min_log_number_to_keep: Option<u64>,
+    /// Oldest blob file its blob references point to.
+    pub oldest_blob_file_number: Option<u64>,
+    /// Unix seconds of the oldest data the file was compacted from, 0 if unknown.
+    pub oldest_ancester_time: u64,
+    /// Unix seconds when the file was written, 0 if unknown.
+    pub file_creation_time: u64,
+    /// Order of L0 files, newer files have larger numbers.
+    pub epoch_number: u64,
+    /// Checksum of the whole file, if enabled.
+    pub file_checksum: String,
+    /// Name of the checksum function.
+    pub file_checksum_func_name: String,
+    /// Storage temperature hint, if set.
+    pub temperature: Option<u8>,
+    /// Stored as raw bytes.
+    pub unique_id: Vec<u8>,
+    /// Range deletion size used for compaction priorities.
+    pub compensated_range_deletion_size: u64,
+    /// Bytes after the data blocks: index, filters and footer.
+    pub tail_size: u64,
+    /// Whether user-defined timestamps are stored in the keys.
+    pub user_defined_timestamps_persisted: bool,
+    /// Stored as raw bytes.
+    pub min_timestamp: Option<Vec<u8>>,
+    /// Stored as raw bytes.
+    pub max_timestamp: Option<Vec<u8>>,
+    /// Deleted by a later record.
+    pub deleted: bool,
+    /// Deleted by a ColumnFamilyDrop.
+    pub dropped_with_column_family: bool,
+}
+
+impl Default for FileMetaData {
+    fn default() -> Self {
+        Self {
+            format: NewFileFormat::NewFile4,
+            column_family: 0,
+            level: 0,
+            file_number: 0,
+            file_size: 0,
+            smallest_key: InternalKey::empty(),
+            largest_key: InternalKey::empty(),
+            smallest_seqno: 0,
+            largest_seqno: 0,
+            path_id: 0,
+            needs_compaction: false,
+            min_log_number_to_keep: None,
+            oldest_blob_file_number: None,
+            oldest_ancester_time: 0,
+            file_creation_time: 0,
+            epoch_number: 0,
+            file_checksum: String::new(),
+            file_checksum_func_name: String::new(),
+            temperature: None,
+            unique_id: Vec::new(),
+            compensated_range_deletion_size: 0,
+            tail_size: 0,
+            user_defined_timestamps_persisted: true, // Default is true
+            min_timestamp: None,
+            max_timestamp: None,
+            deleted: false,
+            dropped_with_column_family: false,
+        }
+    }
+}
+
+impl fmt::Display for FileMetaData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with_keys(f, &BytewiseDecoder)
+    }
+}
+
+impl DisplayWithKeys for FileMetaData {
+    fn fmt_with_keys(&self, f: &mut fmt::Formatter<'_>, keys: &dyn KeyDecoder) -> fmt::Result {
+        let label = ColumnFamilyLabel {
+            id: self.column_family,
+            name: None,
+        };
+        self.fmt_labeled(f, keys, label)
+    }
+}
+
+impl FileMetaData {
+    /// Displays the file with user keys rendered by `keys` and its column
+    /// family printed as `label`, which carries the name.
+    pub fn labeled<'a>(
+        &'a self,
+        keys: &'a dyn KeyDecoder,
+        label: ColumnFamilyLabel<'a>,
+    ) -> LabeledFile<'a> {
+        LabeledFile {
+            meta: self,
+            keys,
+            label,
+        }
+    }
+
+    fn fmt_labeled(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        keys: &dyn KeyDecoder,
+        label: ColumnFamilyLabel<'_>,
+    ) -> fmt::Result {
+        writeln!(f, "FileMetaData {{")?;
+        writeln!(f, "  encoding: {}", self.format)?;
+        writeln!(f, "  column_family: {}", label)?;
+        writeln!(f, "  level: {}", self.level)?;
+        writeln!(f, "  file: {}", self.file_number)?;
+        writeln!(f, "  size: {}", self.file_size)?;
+        if let Some(range) =
+            keys.summarize_range(&self.smallest_key.user_key, &self.largest_key.user_key)
+        {
+            writeln!(f, "  range: {}", range)?;
+        }
+        writeln!(
+            f,
+            "  smallest_key: {}",
+            UserKey(&self.smallest_key.user_key, keys)
+        )?;
+        writeln!(f, "    {}", self.smallest_key.trailer())?;
+        writeln!(
+            f,
+            "  largest_key : {}",
+            UserKey(&self.largest_key.user_key, keys)
+        )?;
+        writeln!(f, "    {}", self.largest_key.trailer())?;
+        writeln!(
+            f,
+            "  seqno: {}..{}",
+            self.smallest_seqno, self.largest_seqno
+        )?;
+
+        if self.path_id != 0 {
+            writeln!(f, "  path_id: {}", self.path_id)?;
+        }
+        if self.needs_compaction {
+            writeln!(f, "  needs_compaction: true")?;
+        }
+        if let Some(num) = self.min_log_number_to_keep {
+            writeln!(f, "  min_log_number_to_keep: {}", num)?;
+        }
+        if let Some(num) = self.oldest_blob_file_number {
+            writeln!(f, "  oldest_blob_file: {}", num)?;
+        }
+        if self.oldest_ancester_time != 0 {
+            writeln!(
+                f,
+                "  oldest_ancester_time: {}",
+                UnixTime(self.oldest_ancester_time)
+            )?;
+        }
+        if self.file_creation_time != 0 {
+            writeln!(
+                f,
+                "  file_creation_time: {}",
+                UnixTime(self.file_creation_time)
+            )?;
+        }
+        if self.epoch_number != 0 {
+            writeln!(f, "  epoch_number: {}", self.epoch_number)?;
+        }
+        if !self.file_checksum.is_empty() {
+            writeln!(f, "  checksum: {}", self.file_checksum)?;
+            writeln!(f, "  checksum_func: {}", self.file_checksum_func_name)?;
+        }
+        if let Some(temp) = self.temperature {
+            writeln!(f, "  temperature: {}", temp)?;
+        }
+        if !self.unique_id.is_empty() {
+            writeln!(f, "  unique_id: {:?}", self.unique_id)?;
+        }
+        if self.compensated_range_deletion_size != 0 {
+            writeln!(
+                f,
+                "  compensated_range_deletion_size: {}",
+                self.compensated_range_deletion_size
+            )?;
+        }
+        if self.tail_size != 0 {
+            writeln!(f, "  tail_size: {}", self.tail_size)?;
+        }
+        if !self.user_defined_timestamps_persisted {
+            writeln!(f, "  user_defined_timestamps_persisted: false")?;
+        }
+        if let Some(ref ts) = self.min_timestamp {
+            writeln!(f, "  min_timestamp: {:?}", ts)?;
+        }
+        if let Some(ref ts) = self.max_timestamp {
+            writeln!(f, "  max_timestamp: {:?}", ts)?;
+        }
+        if self.deleted {
+            writeln!(f, "  deleted: true")?;
+        }
+        if self.dropped_with_column_family {
+            writeln!(f, "  dropped_with_column_family: true")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// A blob file written by integrated BlobDB (kBlobFileAddition).
+#[derive(Debug, Clone)]
+pub struct BlobFileAddition {
+    /// Number of the file, `NNNNNN.blob`.
+    pub blob_file_number: u64,
+    /// Number of blobs in the file.
+    pub total_blob_count: u64,
+    /// Bytes of all blobs in the file.
+    pub total_blob_bytes: u64,
+    /// Name of the checksum function.
+    pub checksum_method: String,
+    /// Checksum of the file.
+    pub checksum_value: Vec<u8>,
+}
+
+impl fmt::Display for BlobFileAddition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "blob file {} count {} bytes {}",
+            self.blob_file_number, self.total_blob_count, self.total_blob_bytes
+        )?;
+        if !self.checksum_method.is_empty() {
+            write!(f, " checksum {} ", self.checksum_method)?;
+            for byte in &self.checksum_value {
+                write!(f, "{:02x}", byte)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Blobs of a blob file which became garbage by a compaction
+/// (kBlobFileGarbage).
+#[derive(Debug, Clone)]
+pub struct BlobFileGarbage {
+    /// Number of the blob file.
+    pub blob_file_number: u64,
+    /// Number of blobs which became garbage.
+    pub garbage_blob_count: u64,
+    /// Bytes of the blobs which became garbage.
+    pub garbage_blob_bytes: u64,
+}
+
+impl fmt::Display for BlobFileGarbage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "blob file {} garbage count {} bytes {}",
+            self.blob_file_number, self.garbage_blob_count, self.garbage_blob_bytes
+        )
+    }
+}
+
+/// Live-file bookkeeping for a blob file: the addition plus all garbage
+/// accumulated for it so far.
+#[derive(Debug, Clone)]
+pub struct BlobFileMetaData {
+    /// The addition the file came from.
+    pub addition: BlobFileAddition,
+    /// From the ColumnFamily edit of the same record.
+    pub column_family: u32,
+    /// Garbage blobs reported so far.
+    pub garbage_blob_count: u64,
+    /// Bytes of the garbage blobs reported so far.
+    pub garbage_blob_bytes: u64,
+    /// Obsolete by a ColumnFamilyDrop.
+    pub dropped_with_column_family: bool,
+}
+
+impl BlobFileMetaData {
+    /// RocksDB drops a blob file from the version once all its blobs are
+    /// garbage, there is no explicit deletion record. Dropping its column
+    /// family makes it obsolete as well.
+    pub fn is_obsolete(&self) -> bool {
+        self.dropped_with_column_family || self.garbage_blob_count >= self.addition.total_blob_count
+    }
+}
+
+impl fmt::Display for BlobFileMetaData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = ColumnFamilyLabel {
+            id: self.column_family,
+            name: None,
+        };
+        self.fmt_labeled(f, label)
+    }
+}
+
+impl BlobFileMetaData {
+    /// Displays the blob file with its column family printed as `label`,
+    /// which carries the name.
+    pub fn labeled<'a>(&'a self, label: ColumnFamilyLabel<'a>) -> LabeledBlobFile<'a> {
+        LabeledBlobFile { meta: self, label }
+    }
+
+    fn fmt_labeled(&self, f: &mut fmt::Formatter<'_>, label: ColumnFamilyLabel<'_>) -> fmt::Result {
+        writeln!(f, "BlobFileMetaData {{")?;
+        writeln!(f, "  file: {}", self.addition.blob_file_number)?;
+        writeln!(f, "  column_family: {}", label)?;
+        writeln!(
+            f,
+            "  blobs: {} ({} bytes)",
+            self.addition.total_blob_count, self.addition.total_blob_bytes
+        )?;
+        writeln!(
+            f,
+            "  garbage: {} ({} bytes)",
+            self.garbage_blob_count, self.garbage_blob_bytes
+        )?;
+        if !self.addition.checksum_method.is_empty() {
+            write!(f, "  checksum: ")?;
+            for byte in &self.addition.checksum_value {
+                write!(f, "{:02x}", byte)?;
+            }
+            writeln!(f)?;
+            writeln!(f, "  checksum_method: {}", self.addition.checksum_method)?;
+        }
+        if self.is_obsolete() {
+            writeln!(f, "  obsolete: true")?;
+        }
+        if self.dropped_with_column_family {
+            writeln!(f, "  dropped_with_column_family: true")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// A file displayed with its column family name, see
+/// [`FileMetaData::labeled`].
+pub struct LabeledFile<'a> {
+    meta: &'a FileMetaData,
+    keys: &'a dyn KeyDecoder,
+    label: ColumnFamilyLabel<'a>,
+}
+
+impl fmt::Display for LabeledFile<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.meta.fmt_labeled(f, self.keys, self.label)
+    }
+}
+
+/// A blob file displayed with its column family name, see
+/// [`BlobFileMetaData::labeled`].
+pub struct LabeledBlobFile<'a> {
+    meta: &'a BlobFileMetaData,
+    label: ColumnFamilyLabel<'a>,
+}
+
+impl fmt::Display for LabeledBlobFile<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.meta.fmt_labeled(f, self.label)
+    }
+}
+
+/// One decoded entry of a manifest record.
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum VersionEdit {
+    /// Name of the comparator of the column family.
+    Comparator(String),
+    /// WALs below this number are not needed by the column family.
+    LogNumber(u64),
+    /// Next number to use for a file.
+    NextFileNumber(u64),
+    /// Last sequence number used.
+    LastSequence(u64),
+    /// Any of the NewFile formats, see meta.format.
+    NewFile4(FileMetaData),
+    /// The column family the record's edits belong to.
+    ColumnFamily(u32),
+    /// Creates the column family with this name.
+    ColumnFamilyAdd(String),
+    /// Log number of the previous manifest, only in old manifests.
+    PrevLogNumber(u64),
+    /// Largest column family id used so far.
+    MaxColumnFamily(u32),
+    /// Level and number of the deleted file.
+    DeletedFile(u32, u64),
+    /// Level and the key the next compaction of that level starts from.
+    CompactCursor(u32, InternalKey),
+    /// WALs below this number are not needed by any column family.
+    MinLogNumberToKeep(u64),
+    /// No additional data needed.
+    ColumnFamilyDrop,
+    /// Remaining entries in the atomic group.
+    InAtomicGroup(u32),
+    /// A blob file added.
+    BlobFileAddition(BlobFileAddition),
+    /// Blobs of a blob file became garbage.
+    BlobFileGarbage(BlobFileGarbage),
+    /// Log number and the synced size, if known.
+    WalAddition(u64, Option<u64>),
+    /// All logs below this number are gone.
+    WalDeletion(u64),
+    /// Unique id of the database.
+    DbId(String),
+    /// Raw user-defined timestamp.
+    FullHistoryTsLow(Vec<u8>),
+    /// Whether user-defined timestamps are persisted.
+    PersistUserDefinedTimestamps(bool),
+    // ... other variants as needed
+}
+
+impl fmt::Display for VersionEdit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with_keys(f, &BytewiseDecoder)
+    }
+}
+
+impl DisplayWithKeys for VersionEdit {
+    fn fmt_with_keys(&self, f: &mut fmt::Formatter<'_>, keys: &dyn KeyDecoder) -> fmt::Result {
+        match self {
+            VersionEdit::Comparator(name) => {
+                write!(f, "Comparator: {}", name)
+            }
+            VersionEdit::LogNumber(num) => {
+                write!(f, "LogNumber: {}", num)
+            }
+            VersionEdit::NextFileNumber(num) => {
+                write!(f, "NextFileNumber: {}", num)
+            }
+            VersionEdit::LastSequence(seq) => {
+                write!(f, "LastSequence: {}", seq)
+            }
+            VersionEdit::NewFile4(meta) => {
+                writeln!(f, "{} {{", meta.format)?;
+                write!(f, "{}", meta.with_keys(keys))?;
+                write!(f, "}}")
+            }
+            VersionEdit::ColumnFamily(id) => {
+                write!(f, "ColumnFamily: {}", id)
+            }
+            VersionEdit::ColumnFamilyAdd(name) => {
+                write!(f, "ColumnFamilyAdd: {}", name)
+            }
+            VersionEdit::PrevLogNumber(num) => {
+                write!(f, "PrevLogNumber: {}", num)
+            }
+            VersionEdit::MaxColumnFamily(num) => {
+                write!(f, "MaxColumnFamily: {}", num)
+            }
+            VersionEdit::DeletedFile(level, file_number) => {
+                write!(f, "DeletedFile: level {} file {}", level, file_number)
+            }
+            VersionEdit::CompactCursor(level, key) => {
+                write!(
+                    f,
+                    "CompactCursor: level {} key {}",
+                    level,
+                    key.with_keys(keys)
+                )
+            }
+            VersionEdit::MinLogNumberToKeep(num) => {
+                write!(f, "MinLogNumberToKeep: {}", num)
+            }
+            VersionEdit::ColumnFamilyDrop => {
+                write!(f, "ColumnFamilyDrop")
+            }
+            VersionEdit::InAtomicGroup(remaining) => {
+                write!(f, "InAtomicGroup: {} remaining", remaining)
+            }
+            VersionEdit::BlobFileAddition(addition) => {
+                write!(f, "BlobFileAddition: {}", addition)
+            }
+            VersionEdit::BlobFileGarbage(garbage) => {
+                write!(f, "BlobFileGarbage: {}", garbage)
+            }
+            VersionEdit::WalAddition(log_number, synced_size) => {
+                write!(f, "WalAddition: log {}", log_number)?;
+                if let Some(size) = synced_size {
+                    write!(f, " synced_size {}", size)?;
+                }
+                Ok(())
+            }
+            VersionEdit::WalDeletion(log_number) => {
+                write!(f, "WalDeletion: logs before {}", log_number)
+            }
+            VersionEdit::DbId(id) => {
+                write!(f, "DbId: {}", id)
+            }
+            VersionEdit::FullHistoryTsLow(ts) => {
+                write!(f, "FullHistoryTsLow: ")?;
+                for byte in ts {
+                    write!(f, "{:02x}", byte)?;
+                }
+                // Timestamps are usually fixed 64-bit little endian integers
+                if ts.len() == 8 {
+                    write!(f, " ({})", (&ts[..]).read_u64::<LittleEndian>().unwrap())?;
+                }
+                Ok(())
+            }
+            VersionEdit::PersistUserDefinedTimestamps(persist) => {
+                write!(f, "PersistUserDefinedTimestamps: {}", persist)
+            }
+        }
+    }
+}
+
+/// Returns the number of records which follow this one in its atomic group,
+/// or None if the record does not belong to an atomic group.
+pub fn atomic_group_remaining(edits: &[VersionEdit]) -> Option<u32> {
+    edits.iter().find_map(|e| match e {
+        VersionEdit::InAtomicGroup(remaining) => Some(*remaining),
+        _ => None,
+    })
+}
+
+/// Returns the column family a record applies to, records without a
+/// ColumnFamily edit belong to the default column family.
+pub fn record_column_family(edits: &[VersionEdit]) -> u32 {
+    edits
+        .iter()
+        .find_map(|e| match e {
+            VersionEdit::ColumnFamily(id) => Some(*id),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_time_falls_back_to_the_number() {
+        assert_eq!(
+            UnixTime(1_767_268_800).to_string(),
+            "2026-01-01 12:00:00 UTC"
+        );
+        assert_eq!(format!("{:#}", UnixTime(0)), "1970-01-01 00:00:00");
+        assert_eq!(UnixTime(u64::MAX).to_string(), u64::MAX.to_string());
+        assert_eq!(UnixTime(1 << 60).to_string(), (1u64 << 60).to_string());
+    }
+}
@@ -0,0 +1,641 @@
+//! Filter expressions selecting files, e.g.
+//! `cf=10 && level=0 && size<2000 && created>2026-01-01`.
+//!
+//! An expression compares fields of a file with values, combined with
+//! `&&`, `||`, `!` and parentheses. Comparisons are `=` (or `==`), `!=`,
+//! `<`, `<=`, `>` and `>=`. Times take unix seconds or
+//! `YYYY-MM-DD[ HH:MM:SS]` in UTC, quoted if they contain a space. Byte
+//! strings take `0x` followed by hex digits, or the plain text. Names like
+//! the format compare without regard to case. Optional fields which are
+//! not set never match, except for `!=`.
+
+use chrono::{NaiveDate, NaiveDateTime};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::compaction::ClassifiedRecord;
+use crate::edit::FileMetaData;
+
+/// Parses a time given as unix seconds or as `YYYY-MM-DD[ HH:MM:SS]` in UTC.
+pub fn parse_time(value: &str) -> Option<u64> {
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(secs);
+    }
+    let dt = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .ok()?;
+    u64::try_from(dt.and_utc().timestamp()).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Number,
+    Time,
+    Bool,
+    Text,
+    Bytes,
+}
+
+/// The fields of `FileMetaData` a filter can refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// [`FileMetaData::format`].
+    Format,
+    /// [`FileMetaData::column_family`].
+    ColumnFamily,
+    /// [`FileMetaData::level`].
+    Level,
+    /// [`FileMetaData::file_number`].
+    FileNumber,
+    /// [`FileMetaData::file_size`].
+    FileSize,
+    /// [`FileMetaData::smallest_key`].
+    SmallestKey,
+    /// [`FileMetaData::largest_key`].
+    LargestKey,
+    /// The value type of [`FileMetaData::smallest_key`].
+    SmallestType,
+    /// The value type of [`FileMetaData::largest_key`].
+    LargestType,
+    /// [`FileMetaData::smallest_seqno`].
+    SmallestSeqno,
+    /// [`FileMetaData::largest_seqno`].
+    LargestSeqno,
+    /// [`FileMetaData::path_id`].
+    PathId,
+    /// [`FileMetaData::needs_compaction`].
+    NeedsCompaction,
+    /// [`FileMetaData::min_log_number_to_keep`].
+    MinLogNumberToKeep,
+    /// [`FileMetaData::oldest_blob_file_number`].
+    OldestBlobFileNumber,
+    /// [`FileMetaData::oldest_ancester_time`].
+    OldestAncesterTime,
+    /// [`FileMetaData::file_creation_time`].
+    FileCreationTime,
+    /// [`FileMetaData::epoch_number`].
+    EpochNumber,
+    /// [`FileMetaData::file_checksum`].
+    FileChecksum,
+    /// [`FileMetaData::file_checksum_func_name`].
+    FileChecksumFuncName,
+    /// [`FileMetaData::temperature`].
+    Temperature,
+    /// [`FileMetaData::unique_id`].
+    UniqueId,
+    /// [`FileMetaData::compensated_range_deletion_size`].
+    CompensatedRangeDeletionSize,
+    /// [`FileMetaData::tail_size`].
+    TailSize,
+    /// [`FileMetaData::user_defined_timestamps_persisted`].
+    UserDefinedTimestampsPersisted,
+    /// [`FileMetaData::min_timestamp`].
+    MinTimestamp,
+    /// [`FileMetaData::max_timestamp`].
+    MaxTimestamp,
+    /// [`FileMetaData::deleted`].
+    Deleted,
+    /// [`FileMetaData::dropped_with_column_family`].
+    DroppedWithColumnFamily,
+}
+
+/// Field names and their aliases, as accepted in expressions.
+pub const FIELD_NAMES: &[(&str, Field)] = &[
+    ("format", Field::Format),
+    ("cf", Field::ColumnFamily),
+    ("column_family", Field::ColumnFamily),
+    ("level", Field::Level),
+    ("file", Field::FileNumber),
+    ("file_number", Field::FileNumber),
+    ("size", Field::FileSize),
+    ("file_size", Field::FileSize),
+    ("smallest_key", Field::SmallestKey),
+    ("largest_key", Field::LargestKey),
+    ("smallest_type", Field::SmallestType),
+    ("largest_type", Field::LargestType),
+    ("smallest_seqno", Field::SmallestSeqno),
+    ("largest_seqno", Field::LargestSeqno),
+    ("path_id", Field::PathId),
+    ("needs_compaction", Field::NeedsCompaction),
+    ("min_log_number_to_keep", Field::MinLogNumberToKeep),
+    ("oldest_blob_file_number", Field::OldestBlobFileNumber),
+    ("oldest_ancester_time", Field::OldestAncesterTime),
+    ("oldest_ancestor_time", Field::OldestAncesterTime),
+    ("created", Field::FileCreationTime),
+    ("file_creation_time", Field::FileCreationTime),
+    ("epoch_number", Field::EpochNumber),
+    ("file_checksum", Field::FileChecksum),
+    ("file_checksum_func_name", Field::FileChecksumFuncName),
+    ("temperature", Field::Temperature),
+    ("unique_id", Field::UniqueId),
+    (
+        "compensated_range_deletion_size",
+        Field::CompensatedRangeDeletionSize,
+    ),
+    ("tail_size", Field::TailSize),
+    (
+        "user_defined_timestamps_persisted",
+        Field::UserDefinedTimestampsPersisted,
+    ),
+    ("min_timestamp", Field::MinTimestamp),
+    ("max_timestamp", Field::MaxTimestamp),
+    ("deleted", Field::Deleted),
+    ("dropped", Field::DroppedWithColumnFamily),
+    ("dropped_with_column_family", Field::DroppedWithColumnFamily),
+];
+
+// The value of a field of one file.
+enum FieldValue<'a> {
+    Number(Option<u64>),
+    Bool(bool),
+    Text(String),
+    Bytes(Option<&'a [u8]>),
+}
+
+impl Field {
+    fn kind(self) -> Kind {
+        match self {
+            Field::Format | Field::SmallestType | Field::LargestType => Kind::Text,
+            Field::FileChecksumFuncName => Kind::Text,
+            Field::SmallestKey | Field::LargestKey | Field::FileChecksum | Field::UniqueId => {
+                Kind::Bytes
+            }
+            Field::MinTimestamp | Field::MaxTimestamp => Kind::Bytes,
+            Field::NeedsCompaction | Field::UserDefinedTimestampsPersisted | Field::Deleted => {
+                Kind::Bool
+            }
+            Field::DroppedWithColumnFamily => Kind::Bool,
+            Field::OldestAncesterTime | Field::FileCreationTime => Kind::Time,
+            _ => Kind::Number,
+        }
+    }
+
+    fn value(self, meta: &FileMetaData) -> FieldValue<'_> {
+        match self {
+            Field::Format => FieldValue::Text(meta.format.to_string()),
+            Field::ColumnFamily => FieldValue::Number(Some(meta.column_family as u64)),
+            Field::Level => FieldValue::Number(Some(meta.level as u64)),
+            Field::FileNumber => FieldValue::Number(Some(meta.file_number)),
+            Field::FileSize => FieldValue::Number(Some(meta.file_size)),
+            Field::SmallestKey => FieldValue::Bytes(Some(&meta.smallest_key.user_key)),
+            Field::LargestKey => FieldValue::Bytes(Some(&meta.largest_key.user_key)),
+            Field::SmallestType => FieldValue::Text(meta.smallest_key.value_type.to_string()),
+            Field::LargestType => FieldValue::Text(meta.largest_key.value_type.to_string()),
+            Field::SmallestSeqno => FieldValue::Number(Some(meta.smallest_seqno)),
+            Field::LargestSeqno => FieldValue::Number(Some(meta.largest_seqno)),
+            Field::PathId => FieldValue::Number(Some(meta.path_id as u64)),
+            Field::NeedsCompaction => FieldValue::Bool(meta.needs_compaction),
+            Field::MinLogNumberToKeep => FieldValue::Number(meta.min_log_number_to_keep),
+            Field::OldestBlobFileNumber => FieldValue::Number(meta.oldest_blob_file_number),
+            Field::OldestAncesterTime => {
+                FieldValue::Number(Some(meta.oldest_ancester_time).filter(|t| *t != 0))
+            }
+            Field::FileCreationTime => {
+                FieldValue::Number(Some(meta.file_creation_time).filter(|t| *t != 0))
+            }
+            Field::EpochNumber => FieldValue::Number(Some(meta.epoch_number)),
+            Field::FileChecksum => FieldValue::Bytes(Some(meta.file_checksum.as_bytes())),
+            Field::FileChecksumFuncName => FieldValue::Text(meta.file_checksum_func_name.clone()),
+            Field::Temperature => FieldValue::Number(meta.temperature.map(u64::from)),
+            Field::UniqueId => FieldValue::Bytes(Some(&meta.unique_id)),
+            Field::CompensatedRangeDeletionSize => {
+                FieldValue::Number(Some(meta.compensated_range_deletion_size))
+            }
+            Field::TailSize => FieldValue::Number(Some(meta.tail_size)),
+            Field::UserDefinedTimestampsPersisted => {
+                FieldValue::Bool(meta.user_defined_timestamps_persisted)
+            }
+            Field::MinTimestamp => FieldValue::Bytes(meta.min_timestamp.as_deref()),
+            Field::MaxTimestamp => FieldValue::Bytes(meta.max_timestamp.as_deref()),
+            Field::Deleted => FieldValue::Bool(meta.deleted),
+            Field::DroppedWithColumnFamily => FieldValue::Bool(meta.dropped_with_column_family),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The comparison operators.
+pub enum Op {
+    /// `==`: Equal.
+    Eq,
+    /// `!=`: Not equal.
+    Ne,
+    /// `<`: Less than.
+    Lt,
+    /// `<=`: At most.
+    Le,
+    /// `>`: Greater than.
+    Gt,
+    /// `>=`: At least.
+    Ge,
+}
+
+impl Op {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// The value a field is compared with, converted to the kind of the field
+/// when parsing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Literal {
+    /// Numbers and times.
+    Number(u64),
+    /// `true` or `false`.
+    Bool(bool),
+    /// Names like the format or value types.
+    Text(String),
+    /// Keys and checksums, from text or `0x` hex.
+    Bytes(Vec<u8>),
+}
+
+/// A parsed filter expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// A field compared with a value.
+    Compare(Field, Op, Literal),
+    /// `!`: the expression does not hold.
+    Not(Box<Filter>),
+    /// `&&`: both expressions hold.
+    And(Box<Filter>, Box<Filter>),
+    /// `||`: any of the expressions holds.
+    Or(Box<Filter>, Box<Filter>),
+}
+
+/// Why a filter expression could not be parsed.
+#[derive(Debug, Clone)]
+pub struct FilterError(String);
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    Op(Op),
+    Word(String),
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, FilterError> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        chars.next();
+        let next = chars.peek().copied();
+        let token = match (c, next) {
+            ('&', Some('&')) | ('|', Some('|')) => {
+                chars.next();
+                if c == '&' {
+                    Token::And
+                } else {
+                    Token::Or
+                }
+            }
+            ('=', Some('=')) | ('!', Some('=')) | ('<', Some('=')) | ('>', Some('=')) => {
+                chars.next();
+                Token::Op(match c {
+                    '=' => Op::Eq,
+                    '!' => Op::Ne,
+                    '<' => Op::Le,
+                    _ => Op::Ge,
+                })
+            }
+            ('=', _) => Token::Op(Op::Eq),
+            ('<', _) => Token::Op(Op::Lt),
+            ('>', _) => Token::Op(Op::Gt),
+            ('!', _) => Token::Not,
+            ('(', _) => Token::Open,
+            (')', _) => Token::Close,
+            ('"', _) | ('\'', _) => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(ch) => word.push(ch),
+                        None => return Err(FilterError("unterminated quote".to_string())),
+                    }
+                }
+                Token::Word(word)
+            }
+            ('&', _) | ('|', _) => {
+                return Err(FilterError(format!("use {}{} to combine conditions", c, c)))
+            }
+            _ => {
+                let mut word = c.to_string();
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_whitespace() || "&|()<>=!\"'".contains(ch) {
+                        break;
+                    }
+                    word.push(ch);
+                    chars.next();
+                }
+                Token::Word(word)
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn parse_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_literal(field: Field, name: &str, value: &str) -> Result<Literal, FilterError> {
+    let invalid = |expected: &str| {
+        FilterError(format!(
+            "invalid value {:?} for {}, expected {}",
+            value, name, expected
+        ))
+    };
+    match field.kind() {
+        Kind::Number => value
+            .parse()
+            .map(Literal::Number)
+            .map_err(|_| invalid("a number")),
+        Kind::Time => parse_time(value)
+            .map(Literal::Number)
+            .ok_or_else(|| invalid("unix seconds or YYYY-MM-DD[ HH:MM:SS]")),
+        Kind::Bool => match value {
+            "true" | "1" => Ok(Literal::Bool(true)),
+            "false" | "0" => Ok(Literal::Bool(false)),
+            _ => Err(invalid("true or false")),
+        },
+        Kind::Text => Ok(Literal::Text(value.to_string())),
+        Kind::Bytes => match value.strip_prefix("0x") {
+            Some(hex) => parse_hex(hex)
+                .map(Literal::Bytes)
+                .ok_or_else(|| invalid("0x followed by hex digits")),
+            None => Ok(Literal::Bytes(value.as_bytes().to_vec())),
+        },
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            filter = Filter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            filter = Filter::And(Box::new(filter), Box::new(self.parse_unary()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter, FilterError> {
+        match self.next() {
+            Some(Token::Not) => Ok(Filter::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Open) => {
+                let filter = self.parse_or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(filter),
+                    _ => Err(FilterError("missing )".to_string())),
+                }
+            }
+            Some(Token::Word(name)) => {
+                let field = FIELD_NAMES
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, field)| *field)
+                    .ok_or_else(|| FilterError(format!("unknown field {:?}", name)))?;
+                let Some(Token::Op(op)) = self.next() else {
+                    return Err(FilterError(format!("expected comparison after {}", name)));
+                };
+                let Some(Token::Word(value)) = self.next() else {
+                    return Err(FilterError(format!("expected value after {}", name)));
+                };
+                Ok(Filter::Compare(
+                    field,
+                    op,
+                    parse_literal(field, &name, &value)?,
+                ))
+            }
+            Some(token) => Err(FilterError(format!("unexpected {:?}", token))),
+            None => Err(FilterError("unexpected end of expression".to_string())),
+        }
+    }
+}
+
+impl Filter {
+    /// Parses a filter expression.
+    pub fn parse(expr: &str) -> Result<Filter, FilterError> {
+        let mut parser = Parser {
+            tokens: tokenize(expr)?,
+            pos: 0,
+        };
+        let filter = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) => Err(FilterError(format!("unexpected {:?}", token))),
+        }
+    }
+
+    /// Tells if a file satisfies the expression.
+    pub fn matches(&self, meta: &FileMetaData) -> bool {
+        match self {
+            Filter::Compare(field, op, literal) => {
+                let ordering = match (field.value(meta), literal) {
+                    (FieldValue::Number(Some(value)), Literal::Number(other)) => value.cmp(other),
+                    (FieldValue::Bool(value), Literal::Bool(other)) => value.cmp(other),
+                    (FieldValue::Text(value), Literal::Text(other)) => {
+                        value.to_ascii_lowercase().cmp(&other.to_ascii_lowercase())
+                    }
+                    (FieldValue::Bytes(Some(value)), Literal::Bytes(other)) => {
+                        value.cmp(other.as_slice())
+                    }
+                    // Optional fields which are not set
+                    _ => return *op == Op::Ne,
+                };
+                op.holds(ordering)
+            }
+            Filter::Not(filter) => !filter.matches(meta),
+            Filter::And(a, b) => a.matches(meta) && b.matches(meta),
+            Filter::Or(a, b) => a.matches(meta) || b.matches(meta),
+        }
+    }
+
+    /// Tells if any file deleted or added by a record satisfies the
+    /// expression. Deleted files are looked up in `files`.
+    pub fn matches_record(
+        &self,
+        record: &ClassifiedRecord,
+        files: &HashMap<u64, FileMetaData>,
+    ) -> bool {
+        record.new_files.iter().any(|f| self.matches(f))
+            || record
+                .deleted_files
+                .iter()
+                .filter_map(|(_, file_number)| files.get(file_number))
+                .any(|f| self.matches(f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::{InternalKey, ValueType};
+
+    fn file() -> FileMetaData {
+        FileMetaData {
+            column_family: 10,
+            level: 0,
+            file_number: 7,
+            file_size: 1500,
+            smallest_key: InternalKey {
+                user_key: b"apple".to_vec(),
+                sequence: 1,
+                value_type: ValueType::Value,
+            },
+            largest_key: InternalKey {
+                user_key: vec![0x70, 0xff],
+                sequence: 2,
+                value_type: ValueType::Deletion,
+            },
+            // 2026-01-01 12:00:00
+            file_creation_time: 1_767_268_800,
+            ..FileMetaData::default()
+        }
+    }
+
+    fn matches(expr: &str) -> bool {
+        Filter::parse(expr).unwrap().matches(&file())
+    }
+
+    fn error(expr: &str) -> String {
+        Filter::parse(expr).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            Filter::parse("cf=1 || level=0 && size<2000").unwrap(),
+            Filter::parse("cf=1 || (level=0 && size<2000)").unwrap()
+        );
+        assert!(matches("cf=1 || level=0 && size<2000"));
+        assert!(!matches("(cf=1 || level=0) && size<1000"));
+    }
+
+    #[test]
+    fn not_and_parentheses() {
+        assert!(matches("!cf=1"));
+        assert!(!matches("!(cf=10 && level=0)"));
+        assert!(matches("!!cf=10"));
+        assert!(matches("((file==7))"));
+    }
+
+    #[test]
+    fn times() {
+        assert!(matches("created>=2026-01-01 && created<2026-01-02"));
+        assert!(matches("created='2026-01-01 12:00:00'"));
+        assert!(matches("created=\"2026-01-01 12:00:00\""));
+        assert!(matches("created=1767268800"));
+        assert!(!matches("created>'2026-01-01 12:00:00'"));
+    }
+
+    #[test]
+    fn byte_literals() {
+        assert!(matches("smallest_key=apple"));
+        assert!(matches("smallest_key=0x6170706c65"));
+        assert!(matches("largest_key=0x70ff"));
+        assert!(matches("largest_key>p"));
+        assert!(!matches("smallest_key>=b"));
+    }
+
+    #[test]
+    fn text_ignores_case() {
+        assert!(matches("smallest_type=value"));
+        assert!(matches("largest_type=DELETION"));
+        assert!(matches("format=newfile4"));
+        // Ordering as well, with case `Value` would sort below `a`
+        assert!(matches("smallest_type>a"));
+    }
+
+    #[test]
+    fn unset_fields_only_match_not_equal() {
+        // Neither temperature nor oldest_ancester_time are set
+        assert!(!matches("temperature=0"));
+        assert!(!matches("temperature<5"));
+        assert!(matches("temperature!=0"));
+        assert!(!matches("oldest_ancester_time<2026-01-01"));
+        assert!(matches("oldest_ancester_time!=0"));
+        assert!(!matches("min_timestamp=0x00"));
+        assert!(matches("min_timestamp!=0x00"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(error("colour=red"), "unknown field \"colour\"");
+        assert_eq!(
+            error("cf=ten"),
+            "invalid value \"ten\" for cf, expected a number"
+        );
+        assert_eq!(
+            error("smallest_key=0x6"),
+            "invalid value \"0x6\" for smallest_key, expected 0x followed by hex digits"
+        );
+        assert_eq!(
+            error("created=yesterday"),
+            "invalid value \"yesterday\" for created, expected unix seconds or YYYY-MM-DD[ HH:MM:SS]"
+        );
+        assert_eq!(error("cf=1 & level=0"), "use && to combine conditions");
+        assert_eq!(error("(cf=1"), "missing )");
+        assert_eq!(error("cf"), "expected comparison after cf");
+        assert_eq!(error("cf="), "expected value after cf");
+        assert_eq!(error("cf=1 level=0"), "unexpected Word(\"level\")");
+        assert_eq!(error("created='2026-01-01"), "unterminated quote");
+        assert_eq!(error(""), "unexpected end of expression");
+    }
+}
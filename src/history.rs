@@ -0,0 +1,244 @@
+//! The lifetime of one file across the manifest: where it was written,
+//! which levels it was moved to and which record removed it.
+
+use std::fmt;
+
+use crate::compaction::{ClassifiedRecord, RecordKind};
+use crate::edit::{FileMetaData, UnixTime};
+use crate::version::{ColumnFamilyLabel, ColumnFamilyNames};
+
+/// What one record did to the file.
+#[derive(Debug, Clone)]
+pub enum FileChange {
+    /// Added by a flush, compaction or ingestion.
+    Created {
+        /// Level the file was added to.
+        level: u32,
+        /// Files rewritten into this one.
+        inputs: Vec<u64>,
+    },
+    /// Moved to another level without rewriting.
+    Moved {
+        /// The old level.
+        from: u32,
+        /// The new level.
+        to: u32,
+    },
+    /// Deleted by a compaction or deletion-only edit.
+    Deleted {
+        /// Level the file was deleted from.
+        level: u32,
+        /// Other files removed by the same record.
+        inputs: Vec<u64>,
+        /// Files the record wrote instead.
+        outputs: Vec<u64>,
+    },
+    /// Deleted because its column family was dropped.
+    DroppedWithColumnFamily,
+}
+
+/// One step in the lifetime of a file.
+#[derive(Debug, Clone)]
+pub struct FileEvent {
+    /// Index of the record.
+    pub index: usize,
+    /// Start of the record in the file.
+    pub offset: u64,
+    /// What wrote the record.
+    pub kind: RecordKind,
+    /// The `file_creation_time` of the files written, if known.
+    pub time: Option<u64>,
+    /// What the record did to the file.
+    pub change: FileChange,
+}
+
+/// Everything the manifest tells about one file, in record order.
+#[derive(Debug, Clone)]
+pub struct FileHistory {
+    /// The file the history is about.
+    pub file_number: u64,
+    /// As last added.
+    pub meta: Option<FileMetaData>,
+    /// Name of the file's column family, if known.
+    pub column_family_name: Option<String>,
+    /// Every record which added, moved or deleted the file.
+    pub events: Vec<FileEvent>,
+}
+
+// Unset creation times are 0.
+fn creation_time(meta: &FileMetaData) -> Option<u64> {
+    Some(meta.file_creation_time).filter(|t| *t != 0)
+}
+
+impl FileHistory {
+    /// Collects the records which added, moved or deleted the file.
+    pub fn build(
+        records: &[ClassifiedRecord],
+        file_number: u64,
+        names: &ColumnFamilyNames,
+    ) -> FileHistory {
+        let mut history = FileHistory {
+            file_number,
+            meta: None,
+            column_family_name: None,
+            events: Vec::new(),
+        };
+        for record in records {
+            let added = record
+                .new_files
+                .iter()
+                .find(|meta| meta.file_number == file_number);
+            let moved = record
+                .moved_files
+                .iter()
+                .find(|(n, _, _)| *n == file_number);
+            let deleted = record.deleted_files.iter().find(|(_, n)| *n == file_number);
+            let (time, change) = match (added, moved, deleted) {
+                (_, Some((_, from, to)), _) => (
+                    None,
+                    FileChange::Moved {
+                        from: *from,
+                        to: *to,
+                    },
+                ),
+                (Some(meta), None, _) => (
+                    creation_time(meta),
+                    FileChange::Created {
+                        level: meta.level,
+                        inputs: record.input_files().map(|(_, n)| *n).collect(),
+                    },
+                ),
+                (None, None, Some((level, _))) => {
+                    let outputs: Vec<&FileMetaData> = record.output_files().collect();
+                    (
+                        outputs.iter().filter_map(|meta| creation_time(meta)).max(),
+                        FileChange::Deleted {
+                            level: *level,
+                            inputs: record
+                                .input_files()
+                                .map(|(_, n)| *n)
+                                .filter(|n| *n != file_number)
+                                .collect(),
+                            outputs: outputs.iter().map(|meta| meta.file_number).collect(),
+                        },
+                    )
+                }
+                (None, None, None) => {
+                    let dropped = record.kind == RecordKind::ColumnFamilyDrop
+                        && history.is_alive()
+                        && history
+                            .meta
+                            .as_ref()
+                            .is_some_and(|meta| meta.column_family == record.column_family);
+                    if !dropped {
+                        continue;
+                    }
+                    (None, FileChange::DroppedWithColumnFamily)
+                }
+            };
+            if let Some(meta) = added {
+                history.meta = Some(meta.clone());
+            }
+            history.events.push(FileEvent {
+                index: record.index,
+                offset: record.offset,
+                kind: record.kind,
+                time,
+                change,
+            });
+        }
+        history.column_family_name = history
+            .meta
+            .as_ref()
+            .and_then(|meta| names.name(meta.column_family))
+            .map(String::from);
+        history
+    }
+
+    /// Tells if the last record touching the file did not delete it.
+    pub fn is_alive(&self) -> bool {
+        self.events.last().is_some_and(|e| {
+            !matches!(
+                e.change,
+                FileChange::Deleted { .. } | FileChange::DroppedWithColumnFamily
+            )
+        })
+    }
+}
+
+fn join(files: &[u64]) -> String {
+    let files: Vec<String> = files.iter().map(|n| n.to_string()).collect();
+    files.join(", ")
+}
+
+impl fmt::Display for FileEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.time {
+            Some(time) => write!(f, "{}", UnixTime(time))?,
+            None => write!(f, "{:<23}", "-")?,
+        }
+        write!(
+            f,
+            "  record {} (offset {:x}, {}): ",
+            self.index, self.offset, self.kind
+        )?;
+        match &self.change {
+            FileChange::Created { level, .. } if self.kind == RecordKind::Snapshot => {
+                write!(f, "on level {} in the snapshot", level)
+            }
+            FileChange::Created { level, inputs } => {
+                write!(f, "created on level {}", level)?;
+                if !inputs.is_empty() {
+                    write!(f, " from files {}", join(inputs))?;
+                }
+                Ok(())
+            }
+            FileChange::Moved { from, to } => {
+                write!(f, "moved from level {} to level {}", from, to)
+            }
+            FileChange::Deleted {
+                level,
+                inputs,
+                outputs,
+            } => {
+                write!(f, "deleted from level {}", level)?;
+                if !inputs.is_empty() {
+                    write!(f, " together with files {}", join(inputs))?;
+                }
+                if !outputs.is_empty() {
+                    write!(f, ", producing files {}", join(outputs))?;
+                }
+                Ok(())
+            }
+            FileChange::DroppedWithColumnFamily => write!(f, "dropped with its column family"),
+        }
+    }
+}
+
+impl fmt::Display for FileHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.events.is_empty() {
+            return write!(
+                f,
+                "File {} does not appear in the manifest",
+                self.file_number
+            );
+        }
+        write!(f, "File {}", self.file_number)?;
+        if let Some(meta) = &self.meta {
+            let cf = ColumnFamilyLabel {
+                id: meta.column_family,
+                name: self.column_family_name.as_deref(),
+            };
+            write!(f, " in column family {}, {} bytes", cf, meta.file_size)?;
+        }
+        write!(f, ":")?;
+        for event in &self.events {
+            write!(f, "\n  {}", event)?;
+        }
+        if self.is_alive() {
+            write!(f, "\n  still alive")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,461 @@
+//! JSON output. The layout of every object is defined here and described
+//! in the README, so it only changes deliberately.
+
+use std::fmt;
+use std::path::Path;
+
+use crate::compaction::RecordKind;
+use crate::edit::{record_column_family, BlobFileMetaData, FileMetaData, InternalKey, VersionEdit};
+use crate::key_decoder::{KeyDecoder, KeyDecoders, UserKey};
+use crate::reader::Record;
+use crate::replay::AtomicGroupPosition;
+use crate::version::{ColumnFamilyNames, ColumnFamilyVersion, LiveFiles, VersionSet};
+
+/// A JSON value. Objects keep the order of their members.
+pub enum Json {
+    /// `null`.
+    Null,
+    /// `true` or `false`.
+    Bool(bool),
+    /// A number, all numbers in manifests are unsigned.
+    Number(u64),
+    /// A string, escaped when written.
+    String(String),
+    /// An array.
+    Array(Vec<Json>),
+    /// An object with its members in order.
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<u64> for Json {
+    fn from(value: u64) -> Self {
+        Json::Number(value)
+    }
+}
+
+impl From<u32> for Json {
+    fn from(value: u32) -> Self {
+        Json::Number(value as u64)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value as u64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(value: Vec<T>) -> Self {
+        Json::Array(value.into_iter().map(Into::into).collect())
+    }
+}
+
+fn fmt_json_string(s: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => fmt_json_string(value, f),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    fmt_json_string(name, f)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Bytes as a lowercase hex string.
+pub fn hex(bytes: &[u8]) -> Json {
+    Json::String(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// An internal key with its raw and decoded user key.
+pub fn key_to_json(key: &InternalKey, keys: &dyn KeyDecoder) -> Json {
+    Json::Object(vec![
+        ("user_key", hex(&key.user_key)),
+        ("decoded", UserKey(&key.user_key, keys).to_string().into()),
+        ("sequence", key.sequence.into()),
+        ("type", key.value_type.to_string().into()),
+    ])
+}
+
+/// All fields of a file.
+pub fn file_to_json(meta: &FileMetaData, keys: &dyn KeyDecoder) -> Json {
+    Json::Object(vec![
+        ("format", meta.format.to_string().into()),
+        ("column_family", meta.column_family.into()),
+        ("level", meta.level.into()),
+        ("file_number", meta.file_number.into()),
+        ("file_size", meta.file_size.into()),
+        ("smallest_key", key_to_json(&meta.smallest_key, keys)),
+        ("largest_key", key_to_json(&meta.largest_key, keys)),
+        ("smallest_seqno", meta.smallest_seqno.into()),
+        ("largest_seqno", meta.largest_seqno.into()),
+        ("path_id", meta.path_id.into()),
+        ("needs_compaction", meta.needs_compaction.into()),
+        ("min_log_number_to_keep", meta.min_log_number_to_keep.into()),
+        (
+            "oldest_blob_file_number",
+            meta.oldest_blob_file_number.into(),
+        ),
+        ("oldest_ancester_time", meta.oldest_ancester_time.into()),
+        ("file_creation_time", meta.file_creation_time.into()),
+        ("epoch_number", meta.epoch_number.into()),
+        ("file_checksum", hex(meta.file_checksum.as_bytes())),
+        (
+            "file_checksum_func_name",
+            meta.file_checksum_func_name.as_str().into(),
+        ),
+        ("temperature", meta.temperature.map(|t| t as u32).into()),
+        ("unique_id", hex(&meta.unique_id)),
+        (
+            "compensated_range_deletion_size",
+            meta.compensated_range_deletion_size.into(),
+        ),
+        ("tail_size", meta.tail_size.into()),
+        (
+            "user_defined_timestamps_persisted",
+            meta.user_defined_timestamps_persisted.into(),
+        ),
+        (
+            "min_timestamp",
+            meta.min_timestamp.as_deref().map_or(Json::Null, hex),
+        ),
+        (
+            "max_timestamp",
+            meta.max_timestamp.as_deref().map_or(Json::Null, hex),
+        ),
+    ])
+}
+
+/// A blob file with its garbage so far.
+pub fn blob_file_to_json(meta: &BlobFileMetaData) -> Json {
+    Json::Object(vec![
+        ("blob_file_number", meta.addition.blob_file_number.into()),
+        ("total_blob_count", meta.addition.total_blob_count.into()),
+        ("total_blob_bytes", meta.addition.total_blob_bytes.into()),
+        ("garbage_blob_count", meta.garbage_blob_count.into()),
+        ("garbage_blob_bytes", meta.garbage_blob_bytes.into()),
+        (
+            "checksum_method",
+            meta.addition.checksum_method.as_str().into(),
+        ),
+        ("checksum_value", hex(&meta.addition.checksum_value)),
+    ])
+}
+
+/// One edit, with its kind as the `kind` member.
+pub fn edit_to_json(edit: &VersionEdit, keys: &dyn KeyDecoder) -> Json {
+    let (kind, mut members): (&str, Vec<(&'static str, Json)>) = match edit {
+        VersionEdit::Comparator(name) => ("Comparator", vec![("name", name.as_str().into())]),
+        VersionEdit::LogNumber(num) => ("LogNumber", vec![("log_number", (*num).into())]),
+        VersionEdit::NextFileNumber(num) => {
+            ("NextFileNumber", vec![("next_file_number", (*num).into())])
+        }
+        VersionEdit::LastSequence(seq) => ("LastSequence", vec![("last_sequence", (*seq).into())]),
+        VersionEdit::NewFile4(meta) => ("NewFile", vec![("file", file_to_json(meta, keys))]),
+        VersionEdit::ColumnFamily(id) => ("ColumnFamily", vec![("column_family", (*id).into())]),
+        VersionEdit::ColumnFamilyAdd(name) => {
+            ("ColumnFamilyAdd", vec![("name", name.as_str().into())])
+        }
+        VersionEdit::PrevLogNumber(num) => ("PrevLogNumber", vec![("log_number", (*num).into())]),
+        VersionEdit::MaxColumnFamily(num) => (
+            "MaxColumnFamily",
+            vec![("max_column_family", (*num).into())],
+        ),
+        VersionEdit::DeletedFile(level, file_number) => (
+            "DeletedFile",
+            vec![
+                ("level", (*level).into()),
+                ("file_number", (*file_number).into()),
+            ],
+        ),
+        VersionEdit::CompactCursor(level, key) => (
+            "CompactCursor",
+            vec![("level", (*level).into()), ("key", key_to_json(key, keys))],
+        ),
+        VersionEdit::MinLogNumberToKeep(num) => {
+            ("MinLogNumberToKeep", vec![("log_number", (*num).into())])
+        }
+        VersionEdit::ColumnFamilyDrop => ("ColumnFamilyDrop", vec![]),
+        VersionEdit::InAtomicGroup(remaining) => {
+            ("InAtomicGroup", vec![("remaining", (*remaining).into())])
+        }
+        VersionEdit::BlobFileAddition(addition) => (
+            "BlobFileAddition",
+            vec![
+                ("blob_file_number", addition.blob_file_number.into()),
+                ("total_blob_count", addition.total_blob_count.into()),
+                ("total_blob_bytes", addition.total_blob_bytes.into()),
+                ("checksum_method", addition.checksum_method.as_str().into()),
+                ("checksum_value", hex(&addition.checksum_value)),
+            ],
+        ),
+        VersionEdit::BlobFileGarbage(garbage) => (
+            "BlobFileGarbage",
+            vec![
+                ("blob_file_number", garbage.blob_file_number.into()),
+                ("garbage_blob_count", garbage.garbage_blob_count.into()),
+                ("garbage_blob_bytes", garbage.garbage_blob_bytes.into()),
+            ],
+        ),
+        VersionEdit::WalAddition(log_number, synced_size) => (
+            "WalAddition",
+            vec![
+                ("log_number", (*log_number).into()),
+                ("synced_size", (*synced_size).into()),
+            ],
+        ),
+        VersionEdit::WalDeletion(log_number) => {
+            ("WalDeletion", vec![("log_number", (*log_number).into())])
+        }
+        VersionEdit::DbId(id) => ("DbId", vec![("db_id", id.as_str().into())]),
+        VersionEdit::FullHistoryTsLow(ts) => ("FullHistoryTsLow", vec![("ts_low", hex(ts))]),
+        VersionEdit::PersistUserDefinedTimestamps(persist) => (
+            "PersistUserDefinedTimestamps",
+            vec![("persist", (*persist).into())],
+        ),
+    };
+    members.insert(0, ("kind", kind.into()));
+    Json::Object(members)
+}
+
+/// A live column family with its files by level.
+pub fn column_family_to_json(cf: &ColumnFamilyVersion, keys: &dyn KeyDecoder) -> Json {
+    let levels = cf
+        .levels
+        .iter()
+        .map(|(level, files)| {
+            Json::Object(vec![
+                ("level", (*level).into()),
+                ("file_count", files.len().into()),
+                (
+                    "size",
+                    files.values().map(|f| f.file_size).sum::<u64>().into(),
+                ),
+                (
+                    "files",
+                    Json::Array(files.values().map(|f| file_to_json(f, keys)).collect()),
+                ),
+            ])
+        })
+        .collect();
+    Json::Object(vec![
+        ("id", cf.id.into()),
+        ("name", cf.name.as_str().into()),
+        ("comparator", cf.comparator.as_deref().into()),
+        ("log_number", cf.log_number.into()),
+        ("file_count", cf.file_count().into()),
+        ("size", cf.total_size().into()),
+        ("levels", Json::Array(levels)),
+    ])
+}
+
+/// Members of the summary object describing the reconstructed versions.
+pub fn version_set_to_json(
+    versions: &VersionSet,
+    decoders: &KeyDecoders,
+) -> Vec<(&'static str, Json)> {
+    vec![
+        ("next_file_number", versions.next_file_number.into()),
+        ("last_sequence", versions.last_sequence.into()),
+        ("prev_log_number", versions.prev_log_number.into()),
+        (
+            "min_log_number_to_keep",
+            versions.min_log_number_to_keep.into(),
+        ),
+        ("max_column_family", versions.max_column_family.into()),
+        (
+            "column_families",
+            Json::Array(
+                versions
+                    .live_column_families()
+                    .map(|cf| column_family_to_json(cf, decoders.for_column_family(cf.id)))
+                    .collect(),
+            ),
+        ),
+    ]
+}
+
+/// The `record` object of one record with its edits.
+pub fn record_to_json(
+    record: &Record,
+    kind: RecordKind,
+    group: Option<AtomicGroupPosition>,
+    names: &ColumnFamilyNames,
+    keys: &dyn KeyDecoder,
+) -> Json {
+    let column_family = record_column_family(&record.edits);
+    Json::Object(vec![
+        ("type", "record".into()),
+        ("index", record.index.into()),
+        ("offset", record.offset.into()),
+        ("length", record.length.into()),
+        ("column_family", column_family.into()),
+        ("column_family_name", names.name(column_family).into()),
+        ("kind", kind.to_string().into()),
+        (
+            "atomic_group",
+            match group {
+                Some(group) => Json::Object(vec![
+                    ("group", group.group.into()),
+                    ("position", group.position.into()),
+                    ("size", group.size.into()),
+                ]),
+                None => Json::Null,
+            },
+        ),
+        (
+            "edits",
+            Json::Array(record.edits.iter().map(|e| edit_to_json(e, keys)).collect()),
+        ),
+    ])
+}
+
+/// The `summary` object at the end of a manifest.
+pub fn summary_to_json(
+    records: &[Record],
+    stopped_at: Option<String>,
+    warnings: Vec<String>,
+    live: &LiveFiles,
+    versions: &VersionSet,
+    names: &ColumnFamilyNames,
+    decoders: &KeyDecoders,
+) -> Json {
+    let mut blob_files: Vec<&BlobFileMetaData> = live
+        .blob_files
+        .values()
+        .filter(|b| !b.is_obsolete())
+        .collect();
+    blob_files.sort_by_key(|b| b.addition.blob_file_number);
+    let mut summary = vec![
+        ("type", "summary".into()),
+        ("records", records.len().into()),
+        ("stopped_at", stopped_at.into()),
+        ("warnings", warnings.into()),
+        ("total_size", live.total_size.into()),
+        ("total_blob_size", live.total_blob_size.into()),
+    ];
+    summary.push((
+        "trivial_moves",
+        Json::Array(
+            live.trivial_moves
+                .iter()
+                .map(|((from, to), (files, bytes))| {
+                    Json::Object(vec![
+                        ("from_level", (*from).into()),
+                        ("to_level", (*to).into()),
+                        ("files", (*files).into()),
+                        ("bytes", (*bytes).into()),
+                    ])
+                })
+                .collect(),
+        ),
+    ));
+    summary.extend(version_set_to_json(versions, decoders));
+    summary.push((
+        "dropped_column_families",
+        Json::Array(
+            names
+                .dropped
+                .iter()
+                .map(|(id, (index, offset))| {
+                    Json::Object(vec![
+                        ("id", (*id).into()),
+                        ("name", names.name(*id).into()),
+                        ("record", (*index).into()),
+                        ("offset", (*offset).into()),
+                    ])
+                })
+                .collect(),
+        ),
+    ));
+    summary.push((
+        "blob_files",
+        Json::Array(blob_files.into_iter().map(blob_file_to_json).collect()),
+    ));
+    summary.push((
+        "wals",
+        Json::Array(
+            live.wals
+                .iter()
+                .map(|(log_number, synced_size)| {
+                    Json::Object(vec![
+                        ("log_number", (*log_number).into()),
+                        ("synced_size", (*synced_size).into()),
+                    ])
+                })
+                .collect(),
+        ),
+    ));
+    Json::Object(summary)
+}
+
+/// The `manifest` object written before each manifest when several are
+/// read.
+pub fn manifest_header_to_json(path: &Path, current: bool) -> Json {
+    Json::Object(vec![
+        ("type", "manifest".into()),
+        ("path", path.display().to_string().into()),
+        ("current", current.into()),
+    ])
+}
@@ -0,0 +1,586 @@
+//! Rendering user keys according to the key layout of a column family.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::arangodb::{ArangoFamily, ArangoFamilyDecoder};
+
+/// Renders user keys in a form which makes sense for the comparator (and
+/// thus the key layout) of a column family.
+pub trait KeyDecoder {
+    /// Name of the decoder, as `--key-decoder` accepts it.
+    fn name(&self) -> &'static str;
+    /// Writes the user key.
+    fn fmt_user_key(&self, key: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    /// One-line description of the key range of a file, for decoders which
+    /// know what the keys refer to.
+    fn summarize_range(&self, _smallest: &[u8], _largest: &[u8]) -> Option<String> {
+        None
+    }
+}
+
+/// Names accepted by `key_decoder_by_name`, e.g. for the command line.
+pub const KEY_DECODER_NAMES: &[&str] = &[
+    "bytewise",
+    "reverse-bytewise",
+    "u64-prefix",
+    "arangodb-vpack",
+    "arangodb-document",
+];
+
+/// Looks up a decoder by one of the `KEY_DECODER_NAMES`.
+pub fn key_decoder_by_name(name: &str) -> Option<Box<dyn KeyDecoder>> {
+    match name {
+        "bytewise" => Some(Box::new(BytewiseDecoder)),
+        "reverse-bytewise" => Some(Box::new(ReverseBytewiseDecoder)),
+        "u64-prefix" => Some(Box::new(U64PrefixDecoder)),
+        "arangodb-vpack" => Some(Box::new(ArangoVPackDecoder)),
+        "arangodb-document" => Some(Box::new(ArangoDocumentDecoder)),
+        _ => None,
+    }
+}
+
+/// Picks the decoder for a comparator name as found in the manifest,
+/// unknown comparators fall back to bytewise rendering.
+pub fn key_decoder_for_comparator(comparator: &str) -> Box<dyn KeyDecoder> {
+    match comparator {
+        "rocksdb.ReverseBytewiseComparator" => Box::new(ReverseBytewiseDecoder),
+        "RocksDBVPackComparator" | "ArangoRocksDBComparator" => Box::new(ArangoVPackDecoder),
+        _ => Box::new(BytewiseDecoder),
+    }
+}
+
+/// Writes the bytes as hex, then the alphanumeric ones as characters.
+pub fn fmt_hex_ascii(key: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for byte in key {
+        write!(f, "{:02x}", byte)?;
+    }
+    write!(f, " ")?;
+    for byte in key {
+        if byte.is_ascii_alphanumeric() {
+            write!(f, "{}", *byte as char)?;
+        } else {
+            write!(f, ".")?;
+        }
+    }
+    Ok(())
+}
+
+/// The big endian number the key starts with.
+pub fn read_u64_be(key: &[u8]) -> Option<u64> {
+    let prefix: [u8; 8] = key.get(..8)?.try_into().ok()?;
+    Some(u64::from_be_bytes(prefix))
+}
+
+/// Hex dump plus printable characters, the classic output of this tool.
+pub struct BytewiseDecoder;
+
+impl KeyDecoder for BytewiseDecoder {
+    fn name(&self) -> &'static str {
+        "bytewise"
+    }
+
+    fn fmt_user_key(&self, key: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_hex_ascii(key, f)
+    }
+}
+
+/// Keys are stored as they are, only the order is reversed.
+pub struct ReverseBytewiseDecoder;
+
+impl KeyDecoder for ReverseBytewiseDecoder {
+    fn name(&self) -> &'static str {
+        "reverse-bytewise"
+    }
+
+    fn fmt_user_key(&self, key: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_hex_ascii(key, f)
+    }
+}
+
+/// Keys starting with a big endian 64-bit number, e.g. a table or tenant id.
+pub struct U64PrefixDecoder;
+
+impl KeyDecoder for U64PrefixDecoder {
+    fn name(&self) -> &'static str {
+        "u64-prefix"
+    }
+
+    fn fmt_user_key(&self, key: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match read_u64_be(key) {
+            Some(prefix) => {
+                write!(f, "[{}] ", prefix)?;
+                fmt_hex_ascii(&key[8..], f)
+            }
+            None => fmt_hex_ascii(key, f),
+        }
+    }
+}
+
+/// ArangoDB index keys: big endian object id followed by the indexed values
+/// as a VelocyPack array, possibly followed by the local document id.
+pub struct ArangoVPackDecoder;
+
+impl KeyDecoder for ArangoVPackDecoder {
+    fn name(&self) -> &'static str {
+        "arangodb-vpack"
+    }
+
+    fn fmt_user_key(&self, key: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(object_id) = read_u64_be(key) else {
+            return fmt_hex_ascii(key, f);
+        };
+        write!(f, "objectId {}", object_id)?;
+        let rest = &key[8..];
+        if rest.is_empty() {
+            return Ok(());
+        }
+        let mut values = String::new();
+        match fmt_vpack(rest, &mut values) {
+            Some(used) => {
+                write!(f, " {}", values)?;
+                let tail = &rest[used..];
+                if let Some(document_id) = read_u64_be(tail).filter(|_| tail.len() == 8) {
+                    write!(f, " documentId {}", document_id)?;
+                } else if !tail.is_empty() {
+                    write!(f, " + ")?;
+                    fmt_hex_ascii(tail, f)?;
+                }
+                Ok(())
+            }
+            None => {
+                write!(f, " ")?;
+                fmt_hex_ascii(rest, f)
+            }
+        }
+    }
+}
+
+/// ArangoDB document keys: big endian object id of the collection followed
+/// by the big endian local document id.
+pub struct ArangoDocumentDecoder;
+
+impl KeyDecoder for ArangoDocumentDecoder {
+    fn name(&self) -> &'static str {
+        "arangodb-document"
+    }
+
+    fn fmt_user_key(&self, key: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(object_id) = read_u64_be(key) else {
+            return fmt_hex_ascii(key, f);
+        };
+        write!(f, "objectId {}", object_id)?;
+        let rest = &key[8..];
+        match read_u64_be(rest) {
+            Some(document_id) if rest.len() == 8 => write!(f, " documentId {}", document_id),
+            _ if rest.is_empty() => Ok(()),
+            _ => {
+                write!(f, " ")?;
+                fmt_hex_ascii(rest, f)
+            }
+        }
+    }
+}
+
+fn read_uint_le(data: &[u8], len: usize) -> Option<u64> {
+    let bytes = data.get(..len)?;
+    Some(
+        bytes
+            .iter()
+            .rev()
+            .fold(0u64, |acc, byte| (acc << 8) | *byte as u64),
+    )
+}
+
+// Renders the VelocyPack value at the start of `data` into `out` and
+// returns its byte length, or None if it is malformed or uses a type we do
+// not decode.
+fn fmt_vpack(data: &[u8], out: &mut String) -> Option<usize> {
+    use std::fmt::Write;
+    let head = *data.first()?;
+    match head {
+        0x01 => {
+            out.push_str("[]");
+            Some(1)
+        }
+        0x02..=0x05 => {
+            // Array of equally sized members without index table
+            let width = 1 << (head - 0x02);
+            let byte_len = read_uint_le(&data[1..], width)? as usize;
+            if byte_len < 1 + width {
+                return None;
+            }
+            let body = data.get(..byte_len)?;
+            let mut pos = 1 + width;
+            while body.get(pos) == Some(&0) {
+                pos += 1;
+            }
+            out.push('[');
+            let mut first = true;
+            while pos < byte_len {
+                if !first {
+                    out.push_str(", ");
+                }
+                first = false;
+                // A member which takes no bytes would never end the loop
+                pos += fmt_vpack(body.get(pos..)?, out).filter(|used| *used > 0)?;
+            }
+            out.push(']');
+            Some(byte_len)
+        }
+        0x06..=0x09 | 0x0b..=0x12 => {
+            // Array or object with index table
+            let is_object = head >= 0x0b;
+            let width = 1 << ((head - if is_object { 0x0b } else { 0x06 }) & 3);
+            let byte_len = read_uint_le(&data[1..], width)? as usize;
+            if byte_len < 1 + width {
+                return None;
+            }
+            let body = data.get(..byte_len)?;
+            let mut pos = 1 + width;
+            let count = if width == 8 {
+                read_uint_le(body.get(byte_len.checked_sub(8)?..)?, 8)? as usize
+            } else {
+                let count = read_uint_le(body.get(pos..)?, width)? as usize;
+                pos += width;
+                count
+            };
+            while body.get(pos) == Some(&0) {
+                pos += 1;
+            }
+            fmt_vpack_items(body.get(pos..)?, count, is_object, out)?;
+            Some(byte_len)
+        }
+        0x0a => {
+            out.push_str("{}");
+            Some(1)
+        }
+        0x13 | 0x14 => {
+            // Compact array or object: varint byte length at the start and
+            // reversed varint item count at the end
+            let (byte_len, len_size) = read_varint(&data[1..])?;
+            let byte_len = byte_len as usize;
+            let body = data.get(..byte_len)?;
+            let mut count: u64 = 0;
+            let mut end = byte_len;
+            let mut shift = 0;
+            loop {
+                end = end.checked_sub(1)?;
+                let byte = *body.get(end)?;
+                count |= ((byte & 0x7f) as u64) << shift;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+                shift += 7;
+            }
+            fmt_vpack_items(
+                body.get(1 + len_size..end)?,
+                count as usize,
+                head == 0x14,
+                out,
+            )?;
+            Some(byte_len)
+        }
+        0x17 => {
+            out.push_str("illegal");
+            Some(1)
+        }
+        0x18 => {
+            out.push_str("null");
+            Some(1)
+        }
+        0x19 => {
+            out.push_str("false");
+            Some(1)
+        }
+        0x1a => {
+            out.push_str("true");
+            Some(1)
+        }
+        0x1b => {
+            let bits = read_uint_le(&data[1..], 8)?;
+            write!(out, "{}", f64::from_bits(bits)).ok()?;
+            Some(9)
+        }
+        0x1c => {
+            let millis = read_uint_le(&data[1..], 8)? as i64;
+            write!(out, "date({})", millis).ok()?;
+            Some(9)
+        }
+        0x1e => {
+            out.push_str("minKey");
+            Some(1)
+        }
+        0x1f => {
+            out.push_str("maxKey");
+            Some(1)
+        }
+        0x20..=0x27 => {
+            let len = (head - 0x1f) as usize;
+            let raw = read_uint_le(&data[1..], len)?;
+            let shift = 64 - 8 * len as u32;
+            write!(out, "{}", ((raw << shift) as i64) >> shift).ok()?;
+            Some(1 + len)
+        }
+        0x28..=0x2f => {
+            let len = (head - 0x27) as usize;
+            write!(out, "{}", read_uint_le(&data[1..], len)?).ok()?;
+            Some(1 + len)
+        }
+        0x30..=0x39 => {
+            write!(out, "{}", head - 0x30).ok()?;
+            Some(1)
+        }
+        0x3a..=0x3f => {
+            write!(out, "{}", head as i64 - 0x40).ok()?;
+            Some(1)
+        }
+        0x40..=0xbe => {
+            let len = (head - 0x40) as usize;
+            fmt_vpack_string(data.get(1..1 + len)?, out);
+            Some(1 + len)
+        }
+        0xbf => {
+            let len = read_uint_le(&data[1..], 8)? as usize;
+            let end = len.checked_add(9)?;
+            fmt_vpack_string(data.get(9..end)?, out);
+            Some(end)
+        }
+        0xc0..=0xc7 => {
+            let width = (head - 0xbf) as usize;
+            let len = read_uint_le(&data[1..], width)? as usize;
+            let end = len.checked_add(1 + width)?;
+            let bytes = data.get(1 + width..end)?;
+            out.push_str("binary(");
+            for byte in bytes {
+                write!(out, "{:02x}", byte).ok()?;
+            }
+            out.push(')');
+            Some(end)
+        }
+        _ => None,
+    }
+}
+
+// Renders `count` consecutive values (or key/value pairs) from `data`.
+fn fmt_vpack_items(data: &[u8], count: usize, is_object: bool, out: &mut String) -> Option<usize> {
+    out.push(if is_object { '{' } else { '[' });
+    let mut pos = 0;
+    for i in 0..count {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        if is_object {
+            pos += fmt_vpack(data.get(pos..)?, out)?;
+            out.push_str(": ");
+        }
+        pos += fmt_vpack(data.get(pos..)?, out)?;
+    }
+    out.push(if is_object { '}' } else { ']' });
+    Some(pos)
+}
+
+fn fmt_vpack_string(bytes: &[u8], out: &mut String) {
+    out.push_str(&format!("{:?}", String::from_utf8_lossy(bytes)));
+}
+
+fn read_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut result: u64 = 0;
+    for (i, byte) in data.iter().enumerate().take(10) {
+        result |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((result, i + 1));
+        }
+    }
+    None
+}
+
+/// Decoders per column family, chosen from the comparator names in the
+/// manifest unless one decoder was forced for all of them. In ArangoDB mode
+/// the decoder follows from the column family instead.
+pub struct KeyDecoders {
+    forced: Option<Box<dyn KeyDecoder>>,
+    arangodb: bool,
+    by_column_family: HashMap<u32, Box<dyn KeyDecoder>>,
+    arango_families: HashMap<u32, ArangoFamilyDecoder>,
+}
+
+impl KeyDecoders {
+    /// Decoders for all column families. `forced` is used for all of them,
+    /// `arangodb` enables the ArangoDB key layouts.
+    pub fn new(forced: Option<Box<dyn KeyDecoder>>, arangodb: bool) -> Self {
+        // Until the manifest tells us the names, guess by creation order
+        let arango_families = (0..)
+            .map_while(|id| {
+                ArangoFamily::from_column_family_id(id)
+                    .map(|family| (id, ArangoFamilyDecoder { family }))
+            })
+            .collect();
+        KeyDecoders {
+            forced,
+            arangodb,
+            by_column_family: HashMap::new(),
+            arango_families,
+        }
+    }
+
+    /// Uses the decoder for the comparator of the column family.
+    pub fn set_comparator(&mut self, column_family: u32, comparator: &str) {
+        self.by_column_family
+            .insert(column_family, key_decoder_for_comparator(comparator));
+    }
+
+    /// Tells the name of a column family, which selects its ArangoDB layout.
+    pub fn set_column_family_name(&mut self, column_family: u32, name: &str) {
+        match ArangoFamily::from_column_family_name(name) {
+            Some(family) => {
+                self.arango_families
+                    .insert(column_family, ArangoFamilyDecoder { family });
+            }
+            None => {
+                self.arango_families.remove(&column_family);
+            }
+        }
+    }
+
+    /// The decoder to render the keys of the column family with.
+    pub fn for_column_family(&self, column_family: u32) -> &dyn KeyDecoder {
+        if let Some(decoder) = &self.forced {
+            return decoder.as_ref();
+        }
+        if self.arangodb {
+            if let Some(decoder) = self.arango_families.get(&column_family) {
+                return decoder;
+            }
+        }
+        match self.by_column_family.get(&column_family) {
+            Some(decoder) => decoder.as_ref(),
+            None => &BytewiseDecoder,
+        }
+    }
+}
+
+/// Displays a user key with the given decoder.
+pub struct UserKey<'a>(pub &'a [u8], pub &'a dyn KeyDecoder);
+
+impl fmt::Display for UserKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.1.fmt_user_key(self.0, f)
+    }
+}
+
+/// Types which print user keys and can thus be displayed with a decoder.
+pub trait DisplayWithKeys {
+    /// Formats the value, with user keys rendered by `keys`.
+    fn fmt_with_keys(&self, f: &mut fmt::Formatter<'_>, keys: &dyn KeyDecoder) -> fmt::Result;
+
+    /// Displays the value with user keys rendered by `keys`.
+    fn with_keys<'a>(&'a self, keys: &'a dyn KeyDecoder) -> WithKeys<'a, Self>
+    where
+        Self: Sized,
+    {
+        WithKeys { value: self, keys }
+    }
+}
+
+/// A value displayed with a key decoder, see `DisplayWithKeys::with_keys`.
+pub struct WithKeys<'a, T> {
+    value: &'a T,
+    keys: &'a dyn KeyDecoder,
+}
+
+impl<T: DisplayWithKeys> fmt::Display for WithKeys<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt_with_keys(f, self.keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arango_key(object_id: u64, rest: &[u8]) -> String {
+        let mut key = object_id.to_be_bytes().to_vec();
+        key.extend_from_slice(rest);
+        UserKey(&key, &ArangoVPackDecoder).to_string()
+    }
+
+    #[test]
+    fn index_values_and_document_id() {
+        // Compact array ["ab", 7] followed by the document id
+        let mut rest = vec![0x13, 0x07, 0x42, b'a', b'b', 0x37, 0x02];
+        rest.extend_from_slice(&42u64.to_be_bytes());
+        assert_eq!(
+            arango_key(5, &rest),
+            r#"objectId 5 ["ab", 7] documentId 42"#
+        );
+    }
+
+    #[test]
+    fn arrays_with_and_without_index_table() {
+        // Equally sized members, one byte length
+        assert_eq!(
+            arango_key(1, &[0x02, 0x04, 0x31, 0x32]),
+            "objectId 1 [1, 2]"
+        );
+        // Index table with one byte offsets
+        assert_eq!(
+            arango_key(1, &[0x06, 0x08, 0x02, 0x18, 0x1a, 0x04, 0x05, 0x00]),
+            "objectId 1 [null, true]"
+        );
+        assert_eq!(arango_key(1, &[0x01]), "objectId 1 []");
+        assert_eq!(arango_key(1, &[0x0a]), "objectId 1 {}");
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(arango_key(1, &[0x3f]), "objectId 1 -1");
+        assert_eq!(arango_key(1, &[0x20, 0xfe]), "objectId 1 -2");
+        assert_eq!(arango_key(1, &[0x29, 0x00, 0x01]), "objectId 1 256");
+        assert_eq!(
+            arango_key(1, &[0xc0, 0x02, 0xab, 0xcd]),
+            "objectId 1 binary(abcd)"
+        );
+        assert_eq!(
+            arango_key(1, &[0xbf, 2, 0, 0, 0, 0, 0, 0, 0, b'h', b'i']),
+            r#"objectId 1 "hi""#
+        );
+    }
+
+    #[test]
+    fn trailing_bytes_are_shown_raw() {
+        assert_eq!(arango_key(1, &[0x31, 0xff]), "objectId 1 1 + ff .");
+    }
+
+    #[test]
+    fn nested_array_without_bytes_is_malformed() {
+        // Used to loop forever
+        assert_eq!(
+            arango_key(1, &[0x02, 0x05, 0x02, 0x00, 0x00]),
+            "objectId 1 0205020000 ....."
+        );
+        assert_eq!(arango_key(1, &[0x06, 0x01]), "objectId 1 0601 ..");
+    }
+
+    #[test]
+    fn lengths_beyond_the_key_are_malformed() {
+        let huge = [0xbf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert_eq!(
+            arango_key(1, &huge),
+            "objectId 1 bfffffffffffffffff ........."
+        );
+        let huge = [0xc7, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert_eq!(
+            arango_key(1, &huge),
+            "objectId 1 c7ffffffffffffffff ........."
+        );
+        assert_eq!(arango_key(1, &[0x45, b'a']), "objectId 1 4561 Ea");
+        assert_eq!(arango_key(1, &[0x13, 0x09, 0x31]), "objectId 1 130931 ..1");
+    }
+
+    #[test]
+    fn short_keys_are_shown_raw() {
+        assert_eq!(UserKey(b"ab", &ArangoVPackDecoder).to_string(), "6162 ab");
+    }
+}
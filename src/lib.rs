@@ -0,0 +1,62 @@
+//! Reading and interpreting RocksDB MANIFEST files.
+//!
+//! A manifest is a log of version edits: which SST and blob files were
+//! added to or removed from which level of which column family, plus
+//! bookkeeping like the next file number and the last sequence number.
+//! [`ManifestReader`] reads the records of a manifest and decodes them
+//! into [`VersionEdit`]s, [`Replay`] applies them the way RocksDB recovery
+//! does and yields the live files and the per column family versions.
+//!
+//! ```no_run
+//! use manifest_dumper::{ManifestReader, Replay};
+//!
+//! let mut reader = ManifestReader::new("MANIFEST-000005")?;
+//! let mut replay = Replay::default();
+//! while let Some(record) = reader.next_record()? {
+//!     replay.push(record);
+//! }
+//! replay.finish();
+//! for cf in replay.versions.live_column_families() {
+//!     println!("{}: {} files, {} bytes", cf.name, cf.file_count(), cf.total_size());
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! User keys are rendered by a [`KeyDecoder`], chosen per column family by
+//! [`KeyDecoders`] from the comparator name or forced by the caller.
+
+#![warn(missing_docs)]
+
+pub mod arangodb;
+pub mod compaction;
+pub mod directory;
+pub mod edit;
+pub mod filter;
+pub mod history;
+pub mod json;
+pub mod key_decoder;
+pub mod lineage;
+pub mod reader;
+pub mod replay;
+pub mod stats;
+pub mod version;
+
+pub use compaction::{classify_records, ClassifiedRecord, RecordClassifier, RecordKind};
+pub use directory::{
+    check_directory, current_manifest, manifest_files, DbFile, DbFileKind, Discrepancy,
+};
+pub use edit::{
+    atomic_group_remaining, record_column_family, BlobFileAddition, BlobFileGarbage,
+    BlobFileMetaData, FileMetaData, InternalKey, NewFileFormat, UnixTime, ValueType, VersionEdit,
+};
+pub use filter::Filter;
+pub use history::{FileChange, FileEvent, FileHistory};
+pub use key_decoder::{DisplayWithKeys, KeyDecoder, KeyDecoders, UserKey};
+pub use lineage::{GraphFormat, Lineage, LineageNode};
+pub use reader::{ManifestReader, Record};
+pub use replay::{AtomicGroupPosition, Replay};
+pub use stats::{ColumnFamilyStats, LevelStats, WindowStats, WriteStats};
+pub use version::{
+    ColumnFamilyLabel, ColumnFamilyNames, ColumnFamilyVersion, LevelShape, LiveFiles, ReplayLimit,
+    VersionSet,
+};
@@ -0,0 +1,228 @@
+//! The lineage of files: which files each compaction rewrote into which,
+//! exported as a Graphviz DOT or Mermaid graph.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+
+use crate::compaction::{ClassifiedRecord, RecordKind};
+use crate::edit::FileMetaData;
+use crate::filter::Filter;
+use crate::version::ColumnFamilyNames;
+
+/// The graph languages a lineage can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT, e.g. for `dot -Tsvg`.
+    Dot,
+    /// Mermaid, e.g. for Markdown.
+    Mermaid,
+}
+
+/// A file in the lineage graph.
+#[derive(Debug, Clone)]
+pub struct LineageNode {
+    /// The file as last added.
+    pub meta: FileMetaData,
+    /// The levels it lived on, moves included.
+    pub levels: Vec<u32>,
+}
+
+/// The file lineage DAG: nodes are files, edges go from the inputs of a
+/// compaction to its outputs.
+#[derive(Debug, Clone, Default)]
+pub struct Lineage {
+    /// The files in the graph by number.
+    pub nodes: BTreeMap<u64, LineageNode>,
+    /// Input and output file numbers of every compaction.
+    pub edges: BTreeSet<(u64, u64)>,
+}
+
+impl Lineage {
+    /// Builds the lineage from classified records. With a filter, only
+    /// compactions with a matching input or output file are included,
+    /// with all their files, plus matching files written otherwise, e.g.
+    /// by flushes.
+    pub fn build(records: &[ClassifiedRecord], filter: Option<&Filter>) -> Lineage {
+        let mut files: BTreeMap<u64, LineageNode> = BTreeMap::new();
+        let mut included: BTreeSet<u64> = BTreeSet::new();
+        let mut edges = BTreeSet::new();
+        for record in records {
+            for meta in &record.new_files {
+                let node = files
+                    .entry(meta.file_number)
+                    .or_insert_with(|| LineageNode {
+                        meta: meta.clone(),
+                        levels: Vec::new(),
+                    });
+                node.meta = meta.clone();
+                node.levels.push(meta.level);
+            }
+            let matches = |n: &u64| {
+                filter.is_none_or(|f| files.get(n).is_some_and(|node| f.matches(&node.meta)))
+            };
+            let inputs: Vec<u64> = record.input_files().map(|(_, n)| *n).collect();
+            let outputs: Vec<u64> = record.output_files().map(|m| m.file_number).collect();
+            if record.kind == RecordKind::Compaction {
+                if inputs.iter().chain(&outputs).any(matches) {
+                    for input in &inputs {
+                        for output in &outputs {
+                            edges.insert((*input, *output));
+                        }
+                    }
+                    included.extend(inputs.iter().chain(&outputs));
+                }
+            } else {
+                included.extend(outputs.into_iter().filter(|n| matches(n)));
+            }
+        }
+        Lineage {
+            nodes: files
+                .into_iter()
+                .filter(|(n, _)| included.contains(n))
+                .collect(),
+            edges,
+        }
+    }
+
+    /// Writes the graph in the given language, with column families
+    /// labelled by name where known.
+    pub fn render(&self, format: GraphFormat, names: &ColumnFamilyNames) -> String {
+        let mut out = String::new();
+        match format {
+            GraphFormat::Dot => self.write_dot(&mut out, names),
+            GraphFormat::Mermaid => self.write_mermaid(&mut out, names),
+        }
+        .unwrap();
+        out
+    }
+
+    fn write_dot(&self, out: &mut String, names: &ColumnFamilyNames) -> fmt::Result {
+        writeln!(out, "digraph lineage {{")?;
+        writeln!(out, "  node [shape=box];")?;
+        for (file_number, node) in &self.nodes {
+            writeln!(
+                out,
+                "  f{} [label=\"{}\"];",
+                file_number,
+                node.label("\\n", escape_dot, names)
+            )?;
+        }
+        for (input, output) in &self.edges {
+            writeln!(out, "  f{} -> f{};", input, output)?;
+        }
+        writeln!(out, "}}")
+    }
+
+    fn write_mermaid(&self, out: &mut String, names: &ColumnFamilyNames) -> fmt::Result {
+        writeln!(out, "graph TD")?;
+        for (file_number, node) in &self.nodes {
+            writeln!(
+                out,
+                "  f{}[\"{}\"]",
+                file_number,
+                node.label("<br/>", escape_mermaid, names)
+            )?;
+        }
+        for (input, output) in &self.edges {
+            writeln!(out, "  f{} --> f{}", input, output)?;
+        }
+        Ok(())
+    }
+}
+
+impl LineageNode {
+    // File number and column family, levels, size. Every line is escaped
+    // for the graph language, the separators are not.
+    fn label(
+        &self,
+        newline: &str,
+        escape: fn(&str) -> String,
+        names: &ColumnFamilyNames,
+    ) -> String {
+        let levels: Vec<String> = self.levels.iter().map(|l| format!("L{}", l)).collect();
+        let lines = [
+            format!(
+                "File {}, cf {}",
+                self.meta.file_number,
+                names.label(self.meta.column_family)
+            ),
+            levels.join(" -> "),
+            format!("{} bytes", self.meta.file_size),
+        ];
+        let lines: Vec<String> = lines.iter().map(|line| escape(line)).collect();
+        lines.join(newline)
+    }
+}
+
+// For a double quoted DOT string.
+fn escape_dot(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// For a double quoted Mermaid label, which may contain HTML: everything
+// special becomes an entity code.
+fn escape_mermaid(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("#quot;"),
+            '#' => escaped.push_str("#35;"),
+            '<' => escaped.push_str("#lt;"),
+            '>' => escaped.push_str("#gt;"),
+            '[' => escaped.push_str("#91;"),
+            ']' => escaped.push_str("#93;"),
+            '\n' => escaped.push_str("<br/>"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dot_labels_are_escaped() {
+        assert_eq!(escape_dot(r#"a "b" \ c"#), r#"a \"b\" \\ c"#);
+        assert_eq!(escape_dot("x\ny"), r"x\ny");
+    }
+
+    #[test]
+    fn mermaid_labels_are_escaped() {
+        assert_eq!(escape_mermaid(r#"a "b" ]c["#), "a #quot;b#quot; #93;c#91;");
+        assert_eq!(escape_mermaid("L0 -> L1 #2"), "L0 -#gt; L1 #35;2");
+    }
+
+    #[test]
+    fn column_family_names_are_escaped_in_graphs() {
+        let mut names = ColumnFamilyNames::default();
+        names.names.insert(3, r#"odd"]name"#.to_string());
+        let mut lineage = Lineage::default();
+        lineage.nodes.insert(
+            7,
+            LineageNode {
+                meta: FileMetaData {
+                    file_number: 7,
+                    column_family: 3,
+                    file_size: 100,
+                    ..Default::default()
+                },
+                levels: vec![0],
+            },
+        );
+        let dot = lineage.render(GraphFormat::Dot, &names);
+        assert!(dot.contains(r#"f7 [label="File 7, cf 3 (odd\"]name)\nL0\n100 bytes"];"#));
+        let mermaid = lineage.render(GraphFormat::Mermaid, &names);
+        assert!(mermaid.contains(r#"f7["File 7, cf 3 (odd#quot;#93;name)<br/>L0<br/>100 bytes"]"#));
+    }
+}
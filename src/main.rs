@@ -1,948 +1,1211 @@
-use byteorder::{LittleEndian, ReadBytesExt};
-use chrono::{DateTime, TimeZone, Utc};
-use crc32c::crc32c;
-use std::collections::HashMap;
-use std::fmt;
-use std::fs::File;
-use std::io::{self, BufReader, Cursor, Read, Seek};
-use std::path::Path;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
-const ZERO_TYPE: u8 = 0;
-const FULL_TYPE: u8 = 1;
-const FIRST_TYPE: u8 = 2;
-const MIDDLE_TYPE: u8 = 3;
-const LAST_TYPE: u8 = 4;
+use manifest_dumper::compaction::{
+    find_deletion_only_edits, group_by_column_family, DeletionThresholds, RecordClassifier,
+    RecordKind, RECORD_KINDS,
+};
+use manifest_dumper::filter::{parse_time, FIELD_NAMES};
+use manifest_dumper::json;
+use manifest_dumper::key_decoder::{key_decoder_by_name, KEY_DECODER_NAMES};
+use manifest_dumper::{
+    check_directory, classify_records, current_manifest, manifest_files, record_column_family,
+    BlobFileMetaData, ColumnFamilyNames, DisplayWithKeys, FileHistory, FileMetaData, Filter,
+    GraphFormat, KeyDecoders, Lineage, LiveFiles, ManifestReader, Record, Replay, ReplayLimit,
+    UnixTime, UserKey, VersionEdit, VersionSet, WriteStats,
+};
 
-const BLOCK_SIZE: u64 = 0x8000;
-const HEADER_SIZE: u64 = 7;
-
-#[derive(Debug)]
-enum Tag {
-    Comparator = 1,
-    LogNumber = 2,
-    NextFileNumber = 3,
-    LastSequence = 4,
-    CompactCursor = 5,
-    DeletedFile = 6,
-    NewFile = 7,
-    PrevLogNumber = 9,
-    MinLogNumberToKeep = 10,
-    // RocksDB-specific formats
-    NewFile2 = 100,
-    NewFile3 = 102,
-    NewFile4 = 103, // Latest format for adding files
-    ColumnFamily = 200,
-    ColumnFamilyAdd = 201,
-    ColumnFamilyDrop = 202,
-    MaxColumnFamily = 203,
-    // ... other tags can be added as needed
+// What to print, selected by the first argument. Without one all sections
+// are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    All,
+    Check,
+    Dump,
+    Files,
+    Alive,
+    Classes,
+    Compactions,
+    Deletions,
+    File,
+    Lineage,
+    Shape,
+    Stats,
+    Summary,
+    Verify,
 }
 
-impl TryFrom<u8> for Tag {
-    type Error = &'static str;
-
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
-        match value {
-            1 => Ok(Tag::Comparator),
-            2 => Ok(Tag::LogNumber),
-            3 => Ok(Tag::NextFileNumber),
-            4 => Ok(Tag::LastSequence),
-            5 => Ok(Tag::CompactCursor),
-            6 => Ok(Tag::DeletedFile),
-            7 => Ok(Tag::NewFile),
-            9 => Ok(Tag::PrevLogNumber),
-            10 => Ok(Tag::MinLogNumberToKeep),
-            100 => Ok(Tag::NewFile2),
-            102 => Ok(Tag::NewFile3),
-            103 => Ok(Tag::NewFile4),
-            200 => Ok(Tag::ColumnFamily),
-            201 => Ok(Tag::ColumnFamilyAdd),
-            202 => Ok(Tag::ColumnFamilyDrop),
-            203 => Ok(Tag::MaxColumnFamily),
-            _ => Err("Invalid tag value"),
-        }
-    }
-}
+const COMMANDS: &[(&str, Command, &str)] = &[
+    (
+        "check",
+        Command::Check,
+        "compare live files with the database directory, exit code 1 on differences",
+    ),
+    ("dump", Command::Dump, "every record with its edits"),
+    (
+        "files",
+        Command::Files,
+        "all data and blob files ever added",
+    ),
+    (
+        "alive",
+        Command::Alive,
+        "files, blob files and WALs alive at the end of the replay",
+    ),
+    (
+        "classes",
+        Command::Classes,
+        "records by kind: flushes, compactions, trivial moves, ...",
+    ),
+    (
+        "compactions",
+        Command::Compactions,
+        "compactions and trivial moves",
+    ),
+    (
+        "deletions",
+        Command::Deletions,
+        "edits which only delete files, by column family",
+    ),
+    (
+        "file",
+        Command::File,
+        "the lifetime of one file, given as file NUMBER",
+    ),
+    (
+        "lineage",
+        Command::Lineage,
+        "graph of the files compactions rewrote into others",
+    ),
+    (
+        "shape",
+        Command::Shape,
+        "per column family table of files, sizes, keys and seqnos by level",
+    ),
+    (
+        "stats",
+        Command::Stats,
+        "bytes flushed and compacted per level, write amplification",
+    ),
+    (
+        "summary",
+        Command::Summary,
+        "totals and the reconstructed LSM tree of every column family",
+    ),
+    (
+        "verify",
+        Command::Verify,
+        "check checksums and consistency, exit code 1 on problems",
+    ),
+];
 
-impl From<Tag> for u8 {
-    fn from(tag: Tag) -> u8 {
-        tag as u8
+impl Command {
+    fn shows(self, section: Command) -> bool {
+        self == Command::All || self == section
     }
 }
 
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
-struct InternalKey {
-    data: Vec<u8>, // For now we just store raw bytes
+struct Options {
+    command: Command,
+    manifest_path: String,
+    forced_decoder: Option<String>, // a name from KEY_DECODER_NAMES
+    arangodb: bool,
+    limit: Option<ReplayLimit>,
+    json: bool,
+    file_number: Option<u64>, // for the file command
+    graph: GraphFormat,       // for the lineage command
+    window: Option<u64>,      // for stats, in seconds
+    db_dir: Option<PathBuf>,  // for check, instead of the manifest's directory
+    all_manifests: bool,      // all manifests of a database directory
+    filter: Option<Filter>,
+    thresholds: DeletionThresholds,
 }
 
-impl fmt::Display for InternalKey {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for byte in &self.data {
-            write!(f, "{:02x}", byte)?;
-        }
-        write!(f, " ")?;
-        for byte in &self.data {
-            if byte.is_ascii_alphanumeric() {
-                write!(f, "{}", *byte as char)?;
-            } else {
-                write!(f, ".")?;
-            }
-        }
-        write!(f, "")
+// Prints the reconstructed LSM tree of every column family.
+fn print_versions(
+    out: &mut impl Write,
+    versions: &VersionSet,
+    names: &ColumnFamilyNames,
+    decoders: &KeyDecoders,
+) -> io::Result<()> {
+    writeln!(out, "============================================")?;
+    writeln!(out, "Reconstructed versions:")?;
+    writeln!(out, "  NextFileNumber: {}", versions.next_file_number)?;
+    writeln!(out, "  LastSequence: {}", versions.last_sequence)?;
+    writeln!(out, "  PrevLogNumber: {}", versions.prev_log_number)?;
+    writeln!(
+        out,
+        "  MinLogNumberToKeep: {}",
+        versions.min_log_number_to_keep
+    )?;
+    writeln!(out, "  MaxColumnFamily: {}", versions.max_column_family)?;
+    for warning in &versions.warnings {
+        writeln!(out, "Warning: {}", warning)?;
     }
-}
-
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
-struct FileMetaData {
-    level: u32,
-    file_number: u64,
-    file_size: u64,
-    smallest_key: InternalKey,
-    largest_key: InternalKey,
-    smallest_seqno: u64,
-    largest_seqno: u64,
-    // Custom fields
-    path_id: u32,
-    needs_compaction: bool,
-    min_log_number_to_keep: Option<u64>,
-    oldest_blob_file_number: Option<u64>,
-    oldest_ancester_time: u64,
-    file_creation_time: u64,
-    epoch_number: u64,
-    file_checksum: String,
-    file_checksum_func_name: String,
-    temperature: Option<u8>,
-    unique_id: Vec<u8>, // For now store as raw bytes
-    compensated_range_deletion_size: u64,
-    tail_size: u64,
-    user_defined_timestamps_persisted: bool,
-    min_timestamp: Option<Vec<u8>>, // Store as raw bytes
-    max_timestamp: Option<Vec<u8>>, // Store as raw bytes
-    deleted: bool,
-}
-
-impl Default for FileMetaData {
-    fn default() -> Self {
-        Self {
-            level: 0,
-            file_number: 0,
-            file_size: 0,
-            smallest_key: InternalKey { data: Vec::new() },
-            largest_key: InternalKey { data: Vec::new() },
-            smallest_seqno: 0,
-            largest_seqno: 0,
-            path_id: 0,
-            needs_compaction: false,
-            min_log_number_to_keep: None,
-            oldest_blob_file_number: None,
-            oldest_ancester_time: 0,
-            file_creation_time: 0,
-            epoch_number: 0,
-            file_checksum: String::new(),
-            file_checksum_func_name: String::new(),
-            temperature: None,
-            unique_id: Vec::new(),
-            compensated_range_deletion_size: 0,
-            tail_size: 0,
-            user_defined_timestamps_persisted: true, // Default is true
-            min_timestamp: None,
-            max_timestamp: None,
-            deleted: false,
-        }
-    }
-}
-
-impl fmt::Display for FileMetaData {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "FileMetaData {{")?;
-        writeln!(f, "  level: {}", self.level)?;
-        writeln!(f, "  file: {}", self.file_number)?;
-        writeln!(f, "  size: {}", self.file_size)?;
-        writeln!(f, "  smallest_key: {}", self.smallest_key)?;
-        writeln!(f, "  largest_key : {}", self.largest_key)?;
+    for cf in versions.live_column_families() {
+        let keys = decoders.for_column_family(cf.id);
+        writeln!(out, "--------------------------------------------")?;
         writeln!(
-            f,
-            "  seqno: {}..{}",
-            self.smallest_seqno, self.largest_seqno
+            out,
+            "Column family {} ({}): {} files, {} bytes",
+            cf.id,
+            cf.name,
+            cf.file_count(),
+            cf.total_size()
         )?;
-
-        if self.path_id != 0 {
-            writeln!(f, "  path_id: {}", self.path_id)?;
+        if let Some(comparator) = &cf.comparator {
+            writeln!(out, "  Comparator: {}", comparator)?;
         }
-        if self.needs_compaction {
-            writeln!(f, "  needs_compaction: true")?;
-        }
-        if let Some(num) = self.min_log_number_to_keep {
-            writeln!(f, "  min_log_number_to_keep: {}", num)?;
-        }
-        if let Some(num) = self.oldest_blob_file_number {
-            writeln!(f, "  oldest_blob_file: {}", num)?;
-        }
-        if self.oldest_ancester_time != 0 {
-            let dt: DateTime<Utc> = Utc
-                .timestamp_opt(self.oldest_ancester_time as i64, 0)
-                .unwrap();
+        writeln!(out, "  LogNumber: {}", cf.log_number)?;
+        for (level, files) in &cf.levels {
+            let size: u64 = files.values().map(|f| f.file_size).sum();
             writeln!(
-                f,
-                "  oldest_ancester_time: {}",
-                dt.format("%Y-%m-%d %H:%M:%S UTC")
+                out,
+                "  Level {}: {} files, {} bytes",
+                level,
+                files.len(),
+                size
             )?;
+            if let Some((smallest, largest)) = cf.key_range(files.values()) {
+                writeln!(out, "    smallest_key: {}", UserKey(smallest, keys))?;
+                writeln!(out, "    largest_key : {}", UserKey(largest, keys))?;
+            }
+            for file in files.values() {
+                writeln!(
+                    out,
+                    "    File {}: {} bytes, seqno {}..{}, keys {} .. {}",
+                    file.file_number,
+                    file.file_size,
+                    file.smallest_seqno,
+                    file.largest_seqno,
+                    UserKey(&file.smallest_key.user_key, keys),
+                    UserKey(&file.largest_key.user_key, keys)
+                )?;
+            }
         }
-        if self.file_creation_time != 0 {
-            let dt: DateTime<Utc> = Utc
-                .timestamp_opt(self.file_creation_time as i64, 0)
-                .unwrap();
+    }
+    if !names.dropped.is_empty() {
+        writeln!(out, "--------------------------------------------")?;
+        writeln!(out, "Dropped column families:")?;
+        for (id, (index, offset)) in &names.dropped {
             writeln!(
-                f,
-                "  file_creation_time: {}",
-                dt.format("%Y-%m-%d %H:%M:%S UTC")
+                out,
+                "  Column family {}: dropped by record #{} at offset {:x}",
+                names.label(*id),
+                index,
+                offset
             )?;
         }
-        if self.epoch_number != 0 {
-            writeln!(f, "  epoch_number: {}", self.epoch_number)?;
-        }
-        if !self.file_checksum.is_empty() {
-            writeln!(f, "  checksum: {}", self.file_checksum)?;
-            writeln!(f, "  checksum_func: {}", self.file_checksum_func_name)?;
-        }
-        if let Some(temp) = self.temperature {
-            writeln!(f, "  temperature: {}", temp)?;
-        }
-        if !self.unique_id.is_empty() {
-            writeln!(f, "  unique_id: {:?}", self.unique_id)?;
-        }
-        if self.compensated_range_deletion_size != 0 {
+    }
+    Ok(())
+}
+
+// One row per level and a sum row for every live column family, keys
+// last since they can be long.
+fn print_shape(
+    out: &mut impl Write,
+    versions: &VersionSet,
+    decoders: &KeyDecoders,
+) -> io::Result<()> {
+    writeln!(out, "============================================")?;
+    writeln!(out, "LSM shape:")?;
+    for cf in versions.live_column_families() {
+        let keys = decoders.for_column_family(cf.id);
+        writeln!(out, "--------------------------------------------")?;
+        writeln!(out, "Column family {} ({}):", cf.id, cf.name)?;
+        writeln!(
+            out,
+            "  {:<5} {:>6} {:>14} {:>12} {:>12} {:<19} {:>7} {:>14}  Keys",
+            "Level",
+            "Files",
+            "Size",
+            "Min seqno",
+            "Max seqno",
+            "Oldest ancestor",
+            "Compact",
+            "RangeDel size"
+        )?;
+        let rows = cf
+            .levels
+            .iter()
+            .map(|(level, files)| (format!("L{}", level), cf.shape(files.values())));
+        let sum = cf.shape(cf.levels.values().flat_map(|files| files.values()));
+        for (name, shape) in rows.chain(std::iter::once(("Sum".to_string(), sum))) {
+            let number = |n: Option<u64>| n.map_or("-".to_string(), |n| n.to_string());
+            let oldest = shape
+                .oldest_ancester_time
+                .map_or("-".to_string(), |t| format!("{:#}", UnixTime(t)));
+            let range = shape
+                .key_range
+                .map_or("-".to_string(), |(smallest, largest)| {
+                    format!("{} .. {}", UserKey(smallest, keys), UserKey(largest, keys))
+                });
             writeln!(
-                f,
-                "  compensated_range_deletion_size: {}",
-                self.compensated_range_deletion_size
+                out,
+                "  {:<5} {:>6} {:>14} {:>12} {:>12} {:<19} {:>7} {:>14}  {}",
+                name,
+                shape.files,
+                shape.size,
+                number(shape.min_seqno),
+                number(shape.max_seqno),
+                oldest,
+                shape.needs_compaction,
+                shape.compensated_range_deletion_size,
+                range
             )?;
         }
-        if self.tail_size != 0 {
-            writeln!(f, "  tail_size: {}", self.tail_size)?;
-        }
-        if !self.user_defined_timestamps_persisted {
-            writeln!(f, "  user_defined_timestamps_persisted: false")?;
-        }
-        if let Some(ref ts) = self.min_timestamp {
-            writeln!(f, "  min_timestamp: {:?}", ts)?;
-        }
-        if let Some(ref ts) = self.max_timestamp {
-            writeln!(f, "  max_timestamp: {:?}", ts)?;
-        }
-        if self.deleted {
-            writeln!(f, "  deleted: true")?;
-        }
-        write!(f, "}}")
     }
+    Ok(())
+}
+
+fn usage_text() -> String {
+    let mut text = String::new();
+    text.push_str("Usage: manifest_dumper [COMMAND] [OPTIONS] MANIFEST|DB_DIR\n");
+    text.push_str("       manifest_dumper file NUMBER [OPTIONS] MANIFEST|DB_DIR\n");
+    text.push_str("A database directory is read through its CURRENT file.\n");
+    text.push_str("Commands (without one all sections are printed):\n");
+    for (name, _, description) in COMMANDS {
+        text.push_str(&format!("  {:<22} {}\n", name, description));
+    }
+    text.push_str("Options:\n");
+    text.push_str("  --format text|json     JSON is available for dump, summary and without a\n");
+    text.push_str("                         command\n");
+    text.push_str("  --all-manifests        read all MANIFEST files of the database directory,\n");
+    text.push_str("                         not with check\n");
+    text.push_str("  --db-dir DIR           database directory to check, by default the one\n");
+    text.push_str("                         holding the manifest\n");
+    text.push_str("  --graph dot|mermaid    language of the lineage graph, DOT by default\n");
+    text.push_str(&format!(
+        "  --key-decoder NAME     render all user keys with one of: {}\n",
+        KEY_DECODER_NAMES.join(", ")
+    ));
+    text.push_str("  --arangodb             decode keys by ArangoDB column family\n");
+    text.push_str("  --filter EXPR          only files, and records with files, matching EXPR,\n");
+    text.push_str(
+        "                         e.g. 'cf=10 && level=0 && size<2000 && created>2026-01-01'\n",
+    );
+    text.push_str("  --max-deleted-file-size N\n");
+    text.push_str("                         deletions removing no file larger than N bytes\n");
+    text.push_str("  --min-deleted-size N   deletions removing at least N bytes\n");
+    text.push_str("  --window DURATION      stats per time window, e.g. 3600, 30m, 6h or 1d\n");
+    text.push_str("Limits to replay only part of the manifest, not with check:\n");
+    text.push_str("  --until-record N       records 0 to N\n");
+    text.push_str("  --until-offset HEX     records starting at or before this offset\n");
+    text.push_str("  --until-sequence N     records up to LastSequence N\n");
+    text.push_str("  --until-time TIME      files created until TIME (unix seconds or\n");
+    text.push_str("                         \"YYYY-MM-DD[ HH:MM:SS]\" in UTC)\n");
+    let fields: Vec<&str> = FIELD_NAMES.iter().map(|(name, _)| *name).collect();
+    text.push_str(&format!("Filter fields: {}\n", fields.join(", ")));
+    text
 }
 
-enum NewFileCustomTag {
-    Terminate = 1,
-    NeedCompaction = 2,
-    MinLogNumberToKeepHack = 3,
-    OldestBlobFileNumber = 4,
-    OldestAncesterTime = 5,
-    FileCreationTime = 6,
-    FileChecksum = 7,
-    FileChecksumFuncName = 8,
-    Temperature = 9,
-    MinTimestamp = 10,
-    MaxTimestamp = 11,
-    UniqueId = 12,
-    EpochNumber = 13,
-    CompensateRangeDeletionSize = 14,
-    TailSize = 15,
-    UserDefinedTimestampsPersisted = 16,
+fn usage() -> ! {
+    eprint!("{}", usage_text());
+    std::process::exit(1);
 }
 
-impl TryFrom<u32> for NewFileCustomTag {
-    type Error = &'static str;
+fn help() -> ! {
+    // Quietly, even if stdout is closed early
+    let _ = io::stdout().lock().write_all(usage_text().as_bytes());
+    std::process::exit(0);
+}
 
-    fn try_from(value: u32) -> Result<Self, Self::Error> {
-        match value {
-            1 => Ok(NewFileCustomTag::Terminate),
-            2 => Ok(NewFileCustomTag::NeedCompaction),
-            3 => Ok(NewFileCustomTag::MinLogNumberToKeepHack),
-            4 => Ok(NewFileCustomTag::OldestBlobFileNumber),
-            5 => Ok(NewFileCustomTag::OldestAncesterTime),
-            6 => Ok(NewFileCustomTag::FileCreationTime),
-            7 => Ok(NewFileCustomTag::FileChecksum),
-            8 => Ok(NewFileCustomTag::FileChecksumFuncName),
-            9 => Ok(NewFileCustomTag::Temperature),
-            10 => Ok(NewFileCustomTag::MinTimestamp),
-            11 => Ok(NewFileCustomTag::MaxTimestamp),
-            12 => Ok(NewFileCustomTag::UniqueId),
-            13 => Ok(NewFileCustomTag::EpochNumber),
-            14 => Ok(NewFileCustomTag::CompensateRangeDeletionSize),
-            15 => Ok(NewFileCustomTag::TailSize),
-            16 => Ok(NewFileCustomTag::UserDefinedTimestampsPersisted),
-            _ => Err("Invalid NewFileCustomTag value"),
+fn parse_limit(flag: &str, value: &str) -> Option<ReplayLimit> {
+    match flag {
+        "--until-record" => value.parse().ok().map(ReplayLimit::Record),
+        "--until-offset" => {
+            let hex = value.trim_start_matches("0x");
+            u64::from_str_radix(hex, 16).ok().map(ReplayLimit::Offset)
         }
+        "--until-sequence" => value.parse().ok().map(ReplayLimit::Sequence),
+        "--until-time" => parse_time(value).map(ReplayLimit::Time),
+        _ => None,
     }
 }
 
-impl From<NewFileCustomTag> for u32 {
-    fn from(tag: NewFileCustomTag) -> u32 {
-        tag as u32
-    }
-}
-#[derive(Debug)]
-#[allow(dead_code)]
-enum VersionEdit {
-    Comparator(String),
-    LogNumber(u64),
-    NextFileNumber(u64),
-    LastSequence(u64),
-    NewFile4(FileMetaData),
-    ColumnFamily(u32),
-    ColumnFamilyAdd(String),
-    PrevLogNumber(u64),
-    MaxColumnFamily(u32),
-    DeletedFile(u32, u64),           // (level, file_number)
-    CompactCursor(u32, InternalKey), // (level, cursor)
-    MinLogNumberToKeep(u64),
-    ColumnFamilyDrop, // no additional data needed
-                      // ... other variants as needed
+// Seconds, or a number with one of the suffixes s, m, h and d.
+fn parse_duration(value: &str) -> Option<u64> {
+    let (number, unit) = match value.char_indices().last()? {
+        (i, 's') => (&value[..i], 1),
+        (i, 'm') => (&value[..i], 60),
+        (i, 'h') => (&value[..i], 3600),
+        (i, 'd') => (&value[..i], 86400),
+        _ => (value, 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(unit)
 }
 
-impl fmt::Display for VersionEdit {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            VersionEdit::Comparator(name) => {
-                write!(f, "Comparator: {}", name)
-            }
-            VersionEdit::LogNumber(num) => {
-                write!(f, "LogNumber: {}", num)
-            }
-            VersionEdit::NextFileNumber(num) => {
-                write!(f, "NextFileNumber: {}", num)
-            }
-            VersionEdit::LastSequence(seq) => {
-                write!(f, "LastSequence: {}", seq)
-            }
-            VersionEdit::NewFile4(meta) => {
-                writeln!(f, "NewFile4 {{")?;
-                write!(f, "{}", meta)?;
-                write!(f, "}}")
-            }
-            VersionEdit::ColumnFamily(id) => {
-                write!(f, "ColumnFamily: {}", id)
-            }
-            VersionEdit::ColumnFamilyAdd(name) => {
-                write!(f, "ColumnFamilyAdd: {}", name)
-            }
-            VersionEdit::PrevLogNumber(num) => {
-                write!(f, "PrevLogNumber: {}", num)
+fn parse_args() -> Options {
+    let mut args = std::env::args().skip(1).peekable();
+    let mut command = Command::All;
+    if let Some(first) = args.peek() {
+        if let Some((_, c, _)) = COMMANDS.iter().find(|(name, _, _)| name == first) {
+            command = *c;
+            args.next();
+        }
+    }
+    let mut file_number = None;
+    if command == Command::File {
+        let number = args.next().unwrap_or_else(|| usage());
+        file_number = Some(number.parse().unwrap_or_else(|_| usage()));
+    }
+    let mut manifest_path = None;
+    let mut forced_decoder = None;
+    let mut arangodb = false;
+    let mut limit = None;
+    let mut json = false;
+    let mut graph = GraphFormat::Dot;
+    let mut window = None;
+    let mut db_dir = None;
+    let mut all_manifests = false;
+    let mut filter = None;
+    let mut thresholds = DeletionThresholds::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" | "-h" | "help" => help(),
+            "--key-decoder" => {
+                let name = args.next().unwrap_or_else(|| usage());
+                if key_decoder_by_name(&name).is_none() {
+                    usage();
+                }
+                forced_decoder = Some(name);
             }
-            VersionEdit::MaxColumnFamily(num) => {
-                write!(f, "MaxColumnFamily: {}", num)
+            "--arangodb" => arangodb = true,
+            "--filter" => {
+                let expr = args.next().unwrap_or_else(|| usage());
+                match Filter::parse(&expr) {
+                    Ok(f) => filter = Some(f),
+                    Err(e) => {
+                        eprintln!("Invalid filter {:?}: {}", expr, e);
+                        std::process::exit(1);
+                    }
+                }
             }
-            VersionEdit::DeletedFile(level, file_number) => {
-                write!(f, "DeletedFile: level {} file {}", level, file_number)
+            "--max-deleted-file-size" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                thresholds.max_file_size = Some(value.parse().unwrap_or_else(|_| usage()));
             }
-            VersionEdit::CompactCursor(level, key) => {
-                write!(f, "CompactCursor: level {} key {}", level, key)
+            "--min-deleted-size" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                thresholds.min_total_size = Some(value.parse().unwrap_or_else(|_| usage()));
             }
-            VersionEdit::MinLogNumberToKeep(num) => {
-                write!(f, "MinLogNumberToKeep: {}", num)
+            "--format" => match args.next().as_deref() {
+                Some("text") => json = false,
+                Some("json") => json = true,
+                _ => usage(),
+            },
+            "--graph" => match args.next().as_deref() {
+                Some("dot") => graph = GraphFormat::Dot,
+                Some("mermaid") => graph = GraphFormat::Mermaid,
+                _ => usage(),
+            },
+            "--all-manifests" => all_manifests = true,
+            "--db-dir" => db_dir = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            "--window" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                window = Some(
+                    parse_duration(&value)
+                        .filter(|w| *w > 0)
+                        .unwrap_or_else(|| usage()),
+                );
             }
-            VersionEdit::ColumnFamilyDrop => {
-                write!(f, "ColumnFamilyDrop")
+            "--until-record" | "--until-offset" | "--until-sequence" | "--until-time" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                limit = Some(parse_limit(&arg, &value).unwrap_or_else(|| usage()));
             }
+            _ if manifest_path.is_none() => manifest_path = Some(arg),
+            _ => usage(),
         }
     }
+    if json && ![Command::All, Command::Dump, Command::Summary].contains(&command) {
+        usage();
+    }
+    // Stale manifests and past states would report files missing or
+    // orphaned which are fine for the current state
+    if all_manifests && command == Command::Check {
+        eprintln!("check only compares the current manifest, --all-manifests is not allowed");
+        std::process::exit(1);
+    }
+    if limit.is_some() && command == Command::Check {
+        eprintln!("check compares the current state, --until-* limits are not allowed");
+        std::process::exit(1);
+    }
+    Options {
+        command,
+        manifest_path: manifest_path.unwrap_or_else(|| usage()),
+        forced_decoder,
+        arangodb,
+        limit,
+        json,
+        file_number,
+        graph,
+        window,
+        db_dir,
+        all_manifests,
+        filter,
+        thresholds,
+    }
 }
 
-struct ManifestReader {
-    reader: BufReader<File>,
-}
-
-fn read_varint32(cursor: &mut Cursor<Vec<u8>>) -> io::Result<u32> {
-    let mut result: u32 = 0;
-    let mut shift = 0;
-    loop {
-        let mut buf = [0u8; 1];
-        cursor.read_exact(&mut buf)?;
-        let byte = buf[0] as u32;
-        result |= (byte & 0x7f) << shift;
-        if byte & 0x80 == 0 {
-            break;
+fn print_files(
+    out: &mut impl Write,
+    live: &LiveFiles,
+    names: &ColumnFamilyNames,
+    decoders: &KeyDecoders,
+    filter: Option<&Filter>,
+) -> io::Result<()> {
+    let mut v: Vec<&FileMetaData> = live.files.values().collect();
+    v.sort_by_key(|a| a.file_number);
+    writeln!(out, "============================================")?;
+    writeln!(out, "List of data files:")?;
+    for (i, meta) in v.iter().enumerate() {
+        if filter.is_some_and(|f| !f.matches(meta)) {
+            continue;
         }
-        shift += 7;
+        writeln!(
+            out,
+            "File #{} of column family {}: {}",
+            i,
+            names.label(meta.column_family),
+            meta.labeled(
+                decoders.for_column_family(meta.column_family),
+                names.label(meta.column_family)
+            )
+        )?;
+        print_level_history(out, live, meta.file_number)?;
     }
-    Ok(result)
-}
-
-fn read_varint64(cursor: &mut Cursor<Vec<u8>>) -> io::Result<u64> {
-    let mut result: u64 = 0;
-    let mut shift = 0;
-    loop {
-        let mut buf = [0u8; 1];
-        cursor.read_exact(&mut buf)?;
-        let byte = buf[0] as u64;
-        result |= (byte & 0x7f) << shift;
-        if byte & 0x80 == 0 {
-            break;
+    if !live.blob_files.is_empty() {
+        let mut bv: Vec<&BlobFileMetaData> = live.blob_files.values().collect();
+        bv.sort_by_key(|b| b.addition.blob_file_number);
+        writeln!(out, "============================================")?;
+        writeln!(out, "List of blob files:")?;
+        for (i, meta) in bv.iter().enumerate() {
+            writeln!(
+                out,
+                "Blob file #{}: {}",
+                i,
+                meta.labeled(names.label(meta.column_family))
+            )?;
         }
-        shift += 7;
     }
-    Ok(result)
-}
-
-fn read_length_prefixed_slice(cursor: &mut Cursor<Vec<u8>>) -> io::Result<Vec<u8>> {
-    let length = read_varint32(cursor)? as usize;
-    let mut data = vec![0u8; length];
-    cursor.read_exact(&mut data)?;
-    Ok(data)
-}
-
-fn unmask_crc(c: u32) -> u32 {
-    let rot = c.wrapping_sub(0xa282ead8u32);
-    (rot >> 17) | (rot << 15)
+    Ok(())
 }
 
-impl ManifestReader {
-    fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let file = File::open(path)?;
-        Ok(ManifestReader {
-            reader: BufReader::new(file),
-        })
+// Only for files which were trivially moved at least once.
+fn print_level_history(out: &mut impl Write, live: &LiveFiles, file_number: u64) -> io::Result<()> {
+    if let Some(levels) = live.level_history.get(&file_number) {
+        if levels.len() > 1 {
+            let levels: Vec<String> = levels.iter().map(|l| l.to_string()).collect();
+            writeln!(out, "  Level history: {}", levels.join(" -> "))?;
+        }
     }
+    Ok(())
+}
 
-    fn position(&mut self) -> u64 {
-        self.reader.stream_position().unwrap()
+fn print_alive(
+    out: &mut impl Write,
+    live: &LiveFiles,
+    names: &ColumnFamilyNames,
+    decoders: &KeyDecoders,
+    filter: Option<&Filter>,
+) -> io::Result<()> {
+    // Numbered like in the list of all files
+    let mut v: Vec<&FileMetaData> = live.files.values().collect();
+    v.sort_by_key(|a| a.file_number);
+    writeln!(out, "============================================")?;
+    writeln!(out, "List of alive files:")?;
+    for (i, meta) in v.iter().enumerate() {
+        if !meta.deleted && filter.is_none_or(|f| f.matches(meta)) {
+            writeln!(
+                out,
+                "Alive file #{} of column family {}: {}",
+                i,
+                names.label(meta.column_family),
+                meta.labeled(
+                    decoders.for_column_family(meta.column_family),
+                    names.label(meta.column_family)
+                )
+            )?;
+            print_level_history(out, live, meta.file_number)?;
+        }
     }
-
-    fn read_record(&mut self) -> io::Result<Option<Vec<VersionEdit>>> {
-        let mut whole_payload: Vec<u8> = Vec::new();
-        loop {
-            let mut left_in_block =
-                BLOCK_SIZE - (self.reader.stream_position().unwrap() % BLOCK_SIZE);
-            if left_in_block < HEADER_SIZE {
-                let mut buf = vec![0u8; left_in_block as usize];
-                let _ = self.reader.read_exact(&mut buf);
-                left_in_block = BLOCK_SIZE;
-            }
-
-            // Read the 7-byte header
-            let mut header = [0u8; 7]; // 4 (crc) + 2 (size) + 1 (type)
-            match self.reader.read_exact(&mut header) {
-                Ok(_) => {}
-                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
-                Err(e) => return Err(e),
-            }
-
-            // Parse header
-            let mut expected_crc = (&header[0..4]).read_u32::<LittleEndian>()?;
-            let size = (&header[4..6]).read_u16::<LittleEndian>()? as usize;
-            let record_type = header[6]; // Should be 1
-
-            // All zero?
-            if expected_crc == 0 && size == 0 && record_type == 0 {
-                let mut buf = vec![0u8; left_in_block as usize];
-                let _ = self.reader.read_exact(&mut buf);
-            }
-
-            expected_crc = unmask_crc(expected_crc);
-            // Read the payload
-            let mut payload = vec![0u8; size];
-            self.reader.read_exact(&mut payload)?;
-
-            // Verify CRC
-            // Create data for CRC calculation: type byte + payload
-            let mut data_for_crc = Vec::with_capacity(1 + size);
-            data_for_crc.push(record_type); // The type byte
-            data_for_crc.extend_from_slice(&payload);
-            let actual_crc = crc32c(&data_for_crc);
-
-            if actual_crc != expected_crc {
-                eprintln!(
-                    "CRC mismatch: expected {:x}, got {:x}, current offset in file: {}, size of last payload: {}",
-                    expected_crc, actual_crc, self.reader.stream_position().unwrap(),
-                    size,
-                );
-            }
-            match record_type {
-                FULL_TYPE => {
-                    whole_payload = payload;
-                    break;
-                }
-                FIRST_TYPE => {
-                    whole_payload = payload;
-                }
-                MIDDLE_TYPE => {
-                    whole_payload.extend_from_slice(&payload);
-                }
-                LAST_TYPE => {
-                    whole_payload.extend_from_slice(&payload);
-                    break;
-                }
-                ZERO_TYPE | _ => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Unexpected record type: {}", record_type),
-                    ));
-                }
+    if !live.blob_files.is_empty() {
+        let mut bv: Vec<&BlobFileMetaData> = live.blob_files.values().collect();
+        bv.sort_by_key(|b| b.addition.blob_file_number);
+        writeln!(out, "============================================")?;
+        writeln!(out, "List of alive blob files:")?;
+        for (i, meta) in bv.iter().enumerate() {
+            if !meta.is_obsolete() {
+                writeln!(
+                    out,
+                    "Alive blob file #{}: {}",
+                    i,
+                    meta.labeled(names.label(meta.column_family))
+                )?;
             }
         }
-
-        // Create a cursor to read from the payload
-        let size = whole_payload.len();
-        let mut cursor = std::io::Cursor::new(whole_payload);
-        let mut edits = Vec::new();
-
-        // Read all items from the payload
-        while cursor.position() < size as u64 {
-            let tag = read_varint32(&mut cursor)?;
-            match Tag::try_from(tag as u8) {
-                Ok(Tag::Comparator) => {
-                    // kComparator
-                    let data = read_length_prefixed_slice(&mut cursor)?;
-                    let comparator = String::from_utf8(data)
-                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-                    edits.push(VersionEdit::Comparator(comparator));
-                }
-                Ok(Tag::LogNumber) => {
-                    // kLogNumber
-                    let log_number = read_varint64(&mut cursor)?;
-                    edits.push(VersionEdit::LogNumber(log_number));
-                }
-                Ok(Tag::NextFileNumber) => {
-                    // kNextFileNumber
-                    let next_file_number = read_varint64(&mut cursor)?;
-                    edits.push(VersionEdit::NextFileNumber(next_file_number));
-                }
-                Ok(Tag::LastSequence) => {
-                    // kLastSequence
-                    let last_sequence = read_varint64(&mut cursor)?;
-                    edits.push(VersionEdit::LastSequence(last_sequence));
-                }
-                Ok(Tag::NewFile) | Ok(Tag::NewFile2) | Ok(Tag::NewFile3) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Obsolete tag: {}", tag),
-                    ));
-                }
-                Ok(Tag::NewFile4) => {
-                    // kNewFile4
-                    let level = read_varint32(&mut cursor)?;
-                    let file_number = read_varint64(&mut cursor)?;
-                    let file_size = read_varint64(&mut cursor)?;
-
-                    let smallest_key_data = read_length_prefixed_slice(&mut cursor)?;
-                    let largest_key_data = read_length_prefixed_slice(&mut cursor)?;
-
-                    let smallest_seqno = read_varint64(&mut cursor)?;
-                    let largest_seqno = read_varint64(&mut cursor)?;
-
-                    let mut meta = FileMetaData::default();
-                    meta.level = level;
-                    meta.file_number = file_number;
-                    meta.file_size = file_size;
-                    meta.smallest_key = InternalKey {
-                        data: smallest_key_data,
-                    };
-                    meta.largest_key = InternalKey {
-                        data: largest_key_data,
-                    };
-                    meta.smallest_seqno = smallest_seqno;
-                    meta.largest_seqno = largest_seqno;
-
-                    // Read custom fields until terminating tag
-                    loop {
-                        let custom_tag = read_varint32(&mut cursor)?;
-                        if custom_tag == NewFileCustomTag::Terminate as u32 {
-                            break;
-                        }
-
-                        let field_data = read_length_prefixed_slice(&mut cursor)?;
-                        match NewFileCustomTag::try_from(custom_tag) {
-                            Ok(NewFileCustomTag::Terminate) => {
-                                // kTerminate
-                                break;
-                            }
-                            Ok(NewFileCustomTag::NeedCompaction) => {
-                                // kNeedCompaction
-                                if field_data.len() != 1 {
-                                    return Err(io::Error::new(
-                                        io::ErrorKind::InvalidData,
-                                        "need_compaction field wrong size",
-                                    ));
-                                }
-                                meta.needs_compaction = field_data[0] == 1;
-                            }
-                            Ok(NewFileCustomTag::MinLogNumberToKeepHack) => {
-                                // kMinLogNumberToKeepHack
-                                let mut field_cursor = Cursor::new(field_data);
-                                meta.min_log_number_to_keep =
-                                    Some(field_cursor.read_u64::<LittleEndian>()?);
-                            }
-                            Ok(NewFileCustomTag::OldestBlobFileNumber) => {
-                                // kOldestBlobFileNumber
-                                let mut field_cursor = Cursor::new(field_data);
-                                meta.oldest_blob_file_number =
-                                    Some(read_varint64(&mut field_cursor)?);
-                            }
-                            Ok(NewFileCustomTag::OldestAncesterTime) => {
-                                // kOldestAncesterTime
-                                let mut field_cursor = Cursor::new(field_data);
-                                meta.oldest_ancester_time = read_varint64(&mut field_cursor)?;
-                            }
-                            Ok(NewFileCustomTag::FileCreationTime) => {
-                                // kFileCreationTime
-                                let mut field_cursor = Cursor::new(field_data);
-                                meta.file_creation_time = read_varint64(&mut field_cursor)?;
-                            }
-                            Ok(NewFileCustomTag::FileChecksum) => {
-                                // kFileChecksum
-                                meta.file_checksum = String::from_utf8(field_data)
-                                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-                            }
-                            Ok(NewFileCustomTag::FileChecksumFuncName) => {
-                                // kFileChecksumFuncName
-                                meta.file_checksum_func_name = String::from_utf8(field_data)
-                                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-                            }
-                            Ok(NewFileCustomTag::Temperature) => {
-                                // kTemperature
-                                if field_data.len() != 1 {
-                                    return Err(io::Error::new(
-                                        io::ErrorKind::InvalidData,
-                                        "temperature field wrong size",
-                                    ));
-                                }
-                                meta.temperature = Some(field_data[0]);
-                            }
-                            Ok(NewFileCustomTag::UniqueId) => {
-                                // kUniqueId
-                                meta.unique_id = field_data;
-                            }
-                            Ok(NewFileCustomTag::EpochNumber) => {
-                                // kEpochNumber
-                                let mut field_cursor = Cursor::new(field_data);
-                                meta.epoch_number = read_varint64(&mut field_cursor)?;
-                            }
-                            Ok(NewFileCustomTag::CompensateRangeDeletionSize) => {
-                                // kCompensatedRangeDeletionSize
-                                let mut field_cursor = Cursor::new(field_data);
-                                meta.compensated_range_deletion_size =
-                                    read_varint64(&mut field_cursor)?;
-                            }
-                            Ok(NewFileCustomTag::TailSize) => {
-                                // kTailSize
-                                let mut field_cursor = Cursor::new(field_data);
-                                meta.tail_size = read_varint64(&mut field_cursor)?;
-                            }
-                            Ok(NewFileCustomTag::UserDefinedTimestampsPersisted) => {
-                                // kUserDefinedTimestampsPersisted
-                                if field_data.len() != 1 {
-                                    return Err(io::Error::new(
-                                        io::ErrorKind::InvalidData,
-                                        "user-defined timestamps persisted field wrong size",
-                                    ));
-                                }
-                                meta.user_defined_timestamps_persisted = field_data[0] == 1;
-                            }
-                            Ok(NewFileCustomTag::MinTimestamp) => {
-                                meta.min_timestamp = Some(field_data);
-                            }
-                            Ok(NewFileCustomTag::MaxTimestamp) => {
-                                meta.max_timestamp = Some(field_data);
-                            }
-                            Err(err) => {
-                                if (custom_tag & 0x40) != 0 {
-                                    // kCustomTagNonSafeIgnoreMask
-                                    return Err(io::Error::new(
-                                        io::ErrorKind::InvalidData,
-                                        format!(
-                                            "new-file4 custom field not supported: {} {}",
-                                            custom_tag, err
-                                        ),
-                                    ));
-                                }
-                                // Safe to ignore this tag
-                            }
-                        }
-                    }
-                    edits.push(VersionEdit::NewFile4(meta));
-                }
-                Ok(Tag::ColumnFamily) => {
-                    // kColumnFamily
-                    let column_family = read_varint32(&mut cursor)?;
-                    edits.push(VersionEdit::ColumnFamily(column_family));
-                }
-                Ok(Tag::ColumnFamilyAdd) => {
-                    // kColumnFamilyAdd
-                    let data = read_length_prefixed_slice(&mut cursor)?;
-                    let column_family_name = String::from_utf8(data)
-                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-                    edits.push(VersionEdit::ColumnFamilyAdd(column_family_name));
-                }
-                Ok(Tag::PrevLogNumber) => {
-                    // kPrevLogNumber
-                    let prev_log_number = read_varint64(&mut cursor)?;
-                    edits.push(VersionEdit::PrevLogNumber(prev_log_number));
-                }
-                Ok(Tag::MaxColumnFamily) => {
-                    // kMaxColumnFamily
-                    let max_column_family = read_varint32(&mut cursor)?;
-                    edits.push(VersionEdit::MaxColumnFamily(max_column_family));
-                }
-                Ok(Tag::DeletedFile) => {
-                    // kDeletedFile
-                    let level = read_varint32(&mut cursor)?;
-                    let file_number = read_varint64(&mut cursor)?;
-                    edits.push(VersionEdit::DeletedFile(level, file_number));
-                }
-                Ok(Tag::CompactCursor) => {
-                    // kCompactCursor
-                    let level = read_varint32(&mut cursor)?;
-                    let cursor_data = read_length_prefixed_slice(&mut cursor)?;
-                    edits.push(VersionEdit::CompactCursor(
-                        level,
-                        InternalKey { data: cursor_data },
-                    ));
-                }
-                Ok(Tag::MinLogNumberToKeep) => {
-                    // kMinLogNumberToKeep
-                    let min_log_number = read_varint64(&mut cursor)?;
-                    edits.push(VersionEdit::MinLogNumberToKeep(min_log_number));
-                }
-                Ok(Tag::ColumnFamilyDrop) => {
-                    // kColumnFamilyDrop
-                    edits.push(VersionEdit::ColumnFamilyDrop);
-                }
-                // ... handle other tags
-                Err(err) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Unknown tag: {} {}", tag, err),
-                    ));
-                }
+    }
+    if !live.wals.is_empty() {
+        writeln!(out, "============================================")?;
+        writeln!(out, "List of alive WALs:")?;
+        for (log_number, synced_size) in &live.wals {
+            match synced_size {
+                Some(size) => writeln!(out, "WAL {}: synced size {}", log_number, size)?,
+                None => writeln!(out, "WAL {}: not synced yet", log_number)?,
             }
         }
-
-        Ok(Some(edits))
     }
+    Ok(())
 }
 
-struct CompactionInfo {
-    start_position: usize, // Position in all_edits where this compaction starts
-    prev_log_number: u64,
-    next_file_number: u64,
-    last_sequence: u64,
-    deleted_files: Vec<(u32, u64)>, // (level, file_number)
-    new_files: Vec<FileMetaData>,
-    column_family: u32,
-    only_deletes: bool,
+fn print_summary(
+    out: &mut impl Write,
+    records: &[Record],
+    live: &LiveFiles,
+    versions: &VersionSet,
+    names: &ColumnFamilyNames,
+    decoders: &KeyDecoders,
+) -> io::Result<()> {
+    writeln!(out, "============================================")?;
+    writeln!(out, "Summary:")?;
+    writeln!(out, "  Records: {}", records.len())?;
+    writeln!(
+        out,
+        "  Alive files: {}",
+        live.files.values().filter(|f| !f.deleted).count()
+    )?;
+    writeln!(out, "  Total size: {}", live.total_size)?;
+    let dropped: Vec<&FileMetaData> = live
+        .files
+        .values()
+        .filter(|f| f.dropped_with_column_family)
+        .collect();
+    if !dropped.is_empty() {
+        writeln!(
+            out,
+            "  Dropped with their column family: {} files, {} bytes",
+            dropped.len(),
+            dropped.iter().map(|f| f.file_size).sum::<u64>()
+        )?;
+    }
+    if !live.blob_files.is_empty() {
+        writeln!(
+            out,
+            "  Alive blob files: {}",
+            live.blob_files
+                .values()
+                .filter(|b| !b.is_obsolete())
+                .count()
+        )?;
+        writeln!(out, "  Total blob size: {}", live.total_blob_size)?;
+    }
+    if !live.wals.is_empty() {
+        writeln!(out, "  Alive WALs: {}", live.wals.len())?;
+    }
+    let (moved_files, moved_bytes) = live.moved();
+    writeln!(
+        out,
+        "  Trivial moves: {} files, {} bytes moved without rewriting",
+        moved_files, moved_bytes
+    )?;
+    for ((from, to), (files, bytes)) in &live.trivial_moves {
+        writeln!(
+            out,
+            "    Level {} -> {}: {} files, {} bytes",
+            from, to, files, bytes
+        )?;
+    }
+    print_versions(out, versions, names, decoders)?;
+    Ok(())
 }
 
-impl fmt::Display for CompactionInfo {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Compaction at position {} {{", self.start_position)?;
-        writeln!(f, "  PrevLogNumber: {}", self.prev_log_number)?;
-        writeln!(f, "  NextFileNumber: {}", self.next_file_number)?;
-        writeln!(f, "  LastSequence: {}", self.last_sequence)?;
-        writeln!(f, "  Deleted files:")?;
-        for (level, file) in &self.deleted_files {
-            writeln!(f, "    Level {}: File {}", level, file)?;
-        }
-        writeln!(f, "  New files:")?;
-        for file in &self.new_files {
-            writeln!(f, "    {}", file)?;
-        }
-        writeln!(f, "  ColumnFamily: {}", self.column_family)?;
-        writeln!(f, "  Only deletes: {}", self.only_deletes)?;
-        write!(f, "}}")
+fn print_compactions(
+    out: &mut impl Write,
+    records: &[Record],
+    live: &LiveFiles,
+    names: &ColumnFamilyNames,
+    decoders: &KeyDecoders,
+    filter: Option<&Filter>,
+) -> io::Result<()> {
+    let compactions: Vec<_> = classify_records(records)
+        .into_iter()
+        .filter(|r| matches!(r.kind, RecordKind::Compaction | RecordKind::TrivialMove))
+        .collect();
+    writeln!(out, "============================================")?;
+    writeln!(
+        out,
+        "\nFound {} compactions and trivial moves:",
+        compactions.len()
+    )?;
+    for (i, compaction) in compactions.iter().enumerate() {
+        if filter.is_some_and(|f| !f.matches_record(compaction, &live.files)) {
+            continue;
+        }
+        writeln!(
+            out,
+            "\nCompaction #{} in column family {}",
+            i + 1,
+            names.label(compaction.column_family)
+        )?;
+        writeln!(
+            out,
+            "{}",
+            compaction.with_keys(decoders.for_column_family(compaction.column_family))
+        )?;
     }
+    Ok(())
 }
 
-fn find_compactions(all_edits: &Vec<Vec<VersionEdit>>) -> Vec<CompactionInfo> {
-    let mut compactions = Vec::new();
-
-    for (position, edits) in all_edits.iter().enumerate() {
-        // Need at least 4 edits for a minimal compaction pattern
-        if edits.len() < 4 {
+// Counts of all record kinds, then one line per record of each kind.
+fn print_classes(
+    out: &mut impl Write,
+    records: &[Record],
+    live: &LiveFiles,
+    names: &ColumnFamilyNames,
+    filter: Option<&Filter>,
+) -> io::Result<()> {
+    let classified: Vec<_> = classify_records(records)
+        .into_iter()
+        .filter(|r| filter.is_none_or(|f| f.matches_record(r, &live.files)))
+        .collect();
+    writeln!(out, "============================================")?;
+    writeln!(out, "Records by kind:")?;
+    for kind in RECORD_KINDS {
+        let of_kind: Vec<_> = classified.iter().filter(|r| r.kind == *kind).collect();
+        let written: u64 = of_kind
+            .iter()
+            .flat_map(|r| r.output_files())
+            .map(|meta| meta.file_size)
+            .sum();
+        writeln!(
+            out,
+            "  {}: {} records, {} bytes written",
+            kind,
+            of_kind.len(),
+            written
+        )?;
+    }
+    for kind in RECORD_KINDS {
+        let of_kind: Vec<_> = classified.iter().filter(|r| r.kind == *kind).collect();
+        if of_kind.is_empty() {
             continue;
         }
+        writeln!(out, "--------------------------------------------")?;
+        writeln!(out, "{} records:", kind)?;
+        for r in of_kind {
+            let input_size: u64 = r
+                .input_files()
+                .filter_map(|(_, n)| live.files.get(n))
+                .map(|meta| meta.file_size)
+                .sum();
+            let output_size: u64 = r.output_files().map(|meta| meta.file_size).sum();
+            writeln!(
+                out,
+                "  Record #{} at offset {:x}: column family {}, {} files deleted ({} bytes), {} added ({} bytes), {} moved",
+                r.index,
+                r.offset,
+                names.label(r.column_family),
+                r.input_files().count(),
+                input_size,
+                r.output_files().count(),
+                output_size,
+                r.moved_files.len()
+            )?;
+        }
+    }
+    Ok(())
+}
 
-        // Check if this could be the start of a compaction pattern
-        let mut iter = edits.iter().enumerate();
-
-        // Try to match the pattern
-        let mut current_compaction = None;
-
-        while let Some((i, edit)) = iter.next() {
-            match edit {
-                // Start of potential compaction pattern
-                VersionEdit::PrevLogNumber(log_num) => {
-                    // Look ahead for required sequence
-                    if let Some(next_file) = edits.get(i + 1) {
-                        if let Some(last_seq) = edits.get(i + 2) {
-                            match (next_file, last_seq) {
-                                (
-                                    VersionEdit::NextFileNumber(next_num),
-                                    VersionEdit::LastSequence(seq),
-                                ) => {
-                                    current_compaction = Some(CompactionInfo {
-                                        start_position: position,
-                                        prev_log_number: *log_num,
-                                        next_file_number: *next_num,
-                                        last_sequence: *seq,
-                                        deleted_files: Vec::new(),
-                                        new_files: Vec::new(),
-                                        column_family: 0, // Will be set later
-                                        only_deletes: false,
-                                    });
-                                    // Skip the next two entries as we've processed them
-                                    iter.next();
-                                    iter.next();
-                                }
-                                _ => {
-                                    current_compaction = None;
-                                }
-                            }
-                        }
-                    }
-                }
-
-                // Collect deleted files if we're in a compaction
-                VersionEdit::DeletedFile(level, file_num) => {
-                    if let Some(ref mut compaction) = current_compaction {
-                        compaction.deleted_files.push((*level, *file_num));
-                    }
-                }
+fn format_ratio(ratio: Option<f64>) -> String {
+    ratio.map_or("-".to_string(), |r| format!("{:.2}", r))
+}
 
-                // Collect new files if we're in a compaction
-                VersionEdit::NewFile4(meta) => {
-                    if let Some(ref mut compaction) = current_compaction {
-                        compaction.new_files.push(meta.clone());
-                    }
-                }
+// Like RocksDB's compaction stats: one row per output level, Rn are the
+// bytes read from the levels above, Rn+1 those read from the level itself.
+fn print_stats(
+    out: &mut impl Write,
+    records: &[Record],
+    names: &ColumnFamilyNames,
+    window: Option<u64>,
+) -> io::Result<()> {
+    let stats = WriteStats::build(&classify_records(records), window);
+    writeln!(out, "============================================")?;
+    writeln!(out, "Write statistics:")?;
+    for (id, cf) in &stats.column_families {
+        writeln!(out, "--------------------------------------------")?;
+        writeln!(
+            out,
+            "Column family {}: {} flushes, {} bytes flushed, {} bytes ingested",
+            names.label(*id),
+            cf.flushes,
+            cf.flushed,
+            cf.ingested
+        )?;
+        writeln!(
+            out,
+            "  {:<5} {:>11} {:>14} {:>14} {:>14} {:>14} {:>7}",
+            "Level", "Compactions", "Read Rn", "Read Rn+1", "Written", "Moved in", "W-Amp"
+        )?;
+        let rows = cf
+            .levels
+            .iter()
+            .map(|(level, s)| (format!("L{}", level), *s));
+        for (name, level) in rows.chain(std::iter::once(("Sum".to_string(), cf.sum()))) {
+            let write_amplification = if name == "Sum" {
+                cf.write_amplification()
+            } else {
+                level.write_amplification()
+            };
+            writeln!(
+                out,
+                "  {:<5} {:>11} {:>14} {:>14} {:>14} {:>14} {:>7}",
+                name,
+                level.compactions,
+                level.read_from_previous,
+                level.read_from_level,
+                level.written,
+                level.moved_in,
+                format_ratio(write_amplification)
+            )?;
+        }
+    }
+    if let Some(length) = stats.window {
+        writeln!(out, "--------------------------------------------")?;
+        writeln!(out, "By time window of {} seconds:", length)?;
+        writeln!(
+            out,
+            "  {:<23} {:>14} {:>14} {:>14} {:>7}",
+            "Window start", "Flushed", "Compacted in", "Compacted out", "W-Amp"
+        )?;
+        for (start, window) in &stats.windows {
+            let start = match start {
+                Some(start) => UnixTime(*start).to_string(),
+                None => "unknown".to_string(),
+            };
+            writeln!(
+                out,
+                "  {:<23} {:>14} {:>14} {:>14} {:>7}",
+                start,
+                window.flushed,
+                window.compaction_read,
+                window.compaction_written,
+                format_ratio(window.write_amplification())
+            )?;
+        }
+    }
+    Ok(())
+}
 
-                // End of compaction pattern
-                VersionEdit::ColumnFamily(cf_id) => {
-                    if let Some(mut compaction) = current_compaction.take() {
-                        compaction.column_family = *cf_id;
-                        // Validate that this looks like a real compaction
-                        if compaction.new_files.is_empty() {
-                            compaction.only_deletes = true;
-                        }
-                        if !compaction.deleted_files.is_empty() || !compaction.new_files.is_empty()
-                        {
-                            compactions.push(compaction);
-                        }
-                    }
+fn print_deletions(
+    out: &mut impl Write,
+    records: &[Record],
+    versions: &VersionSet,
+    names: &ColumnFamilyNames,
+    decoders: &KeyDecoders,
+    filter: Option<&Filter>,
+    thresholds: DeletionThresholds,
+) -> io::Result<()> {
+    let by_column_family = group_by_column_family(
+        find_deletion_only_edits(records)
+            .into_iter()
+            .filter(|edit| {
+                filter.is_none_or(|f| {
+                    edit.removed
+                        .iter()
+                        .filter_map(|r| r.meta.as_ref())
+                        .any(|meta| f.matches(meta))
+                })
+            })
+            .filter(|edit| thresholds.accepts(edit)),
+    );
+    writeln!(out, "============================================")?;
+    writeln!(out, "Deletion-only edits:")?;
+    for (id, edits) in &by_column_family {
+        let keys = decoders.for_column_family(*id);
+        let cf = versions.column_families.get(id);
+        writeln!(out, "--------------------------------------------")?;
+        writeln!(
+            out,
+            "Column family {}: {} edits, {} files, {} bytes",
+            names.label(*id),
+            edits.len(),
+            edits.iter().map(|e| e.removed.len()).sum::<usize>(),
+            edits.iter().map(|e| e.total_size()).sum::<u64>()
+        )?;
+        for edit in edits {
+            writeln!(
+                out,
+                "  Record #{} at offset {:x}: {} files, {} bytes",
+                edit.index,
+                edit.offset,
+                edit.removed.len(),
+                edit.total_size()
+            )?;
+            let metas = edit.removed.iter().filter_map(|r| r.meta.as_ref());
+            if let Some((smallest, largest)) = cf.and_then(|cf| cf.key_range(metas)) {
+                writeln!(
+                    out,
+                    "    keys {} .. {}",
+                    UserKey(smallest, keys),
+                    UserKey(largest, keys)
+                )?;
+            }
+            for removed in &edit.removed {
+                match &removed.meta {
+                    Some(meta) => writeln!(
+                        out,
+                        "    Level {}: File {}, {} bytes, keys {} .. {}",
+                        removed.level,
+                        removed.file_number,
+                        meta.file_size,
+                        UserKey(&meta.smallest_key.user_key, keys),
+                        UserKey(&meta.largest_key.user_key, keys)
+                    )?,
+                    None => writeln!(
+                        out,
+                        "    Level {}: File {}, not added before",
+                        removed.level, removed.file_number
+                    )?,
                 }
-
-                _ => {}
             }
         }
     }
-
-    compactions
+    Ok(())
 }
 
-fn main() -> io::Result<()> {
-    let manifest_path = std::env::args()
-        .nth(1)
-        .expect("Please provide path to MANIFEST file");
+// Replays one manifest and prints what the command asks for. Returns false
+// if verify or check found problems.
+fn run(out: &mut impl Write, options: &Options, manifest_path: &Path) -> io::Result<bool> {
+    let command = options.command;
+    let json = options.json;
+    let verify = command == Command::Verify;
+    let dump = command.shows(Command::Dump);
+    // The graph goes to stdout alone, to be piped into dot:
+    let lineage = command == Command::Lineage;
 
     let mut reader = ManifestReader::new(manifest_path)?;
+    let forced_decoder = options
+        .forced_decoder
+        .as_deref()
+        .and_then(key_decoder_by_name);
+    let mut decoders = KeyDecoders::new(forced_decoder, options.arangodb);
+    // The default column family is never added explicitly
+    decoders.set_column_family_name(0, "default");
+    let mut names = ColumnFamilyNames::default();
 
-    let mut files: HashMap<u64, FileMetaData> = HashMap::new();
-
-    let mut pos: u64 = 0;
-    let mut all_edits: Vec<Vec<VersionEdit>> = Vec::new();
-    let mut total_size: u64 = 0;
-    while let Some(edit) = reader.read_record()? {
-        let newpos = reader.position();
-        println!("---------------------------------------------------");
-        println!("New edits: {:x} {:x}", pos, newpos - pos);
-        pos = newpos;
+    let mut replay = Replay::default();
+    let mut classifier = RecordClassifier::default();
+    // Warnings about the replay as a whole, JSON output collects all
+    // warnings for the summary:
+    let mut warnings: Vec<String> = Vec::new();
+    // Everything verify complains about:
+    let mut problems: Vec<String> = Vec::new();
+    let mut stopped_at = None;
+    loop {
+        let offset = reader.position()?;
+        let next = reader.next_record();
+        for warning in reader.take_warnings() {
+            if verify {
+                problems.push(warning);
+            } else {
+                eprintln!("{}", warning);
+            }
+        }
+        let record = match next {
+            Ok(Some(record)) => record,
+            Ok(None) => break,
+            Err(e) if verify => {
+                problems.push(format!(
+                    "Record #{} at offset {:x} cannot be read: {}",
+                    replay.records.len(),
+                    offset,
+                    e
+                ));
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        if let Some(limit) = options.limit {
+            if limit.stops_before(&record) {
+                let message = format!(
+                    "Replay stopped at {}, before record #{} at offset {:x}",
+                    limit, record.index, record.offset
+                );
+                if lineage {
+                    eprintln!("{}", message);
+                } else if !json {
+                    writeln!(out, "============================================")?;
+                    writeln!(out, "{}", message)?;
+                }
+                stopped_at = Some(message);
+                break;
+            }
+        }
+        if dump && !json {
+            writeln!(out, "---------------------------------------------------")?;
+            writeln!(out, "New edits: {:x} {:x}", record.offset, record.length)?;
+        }
 
-        for e in &edit {
-            println!("  {}", e);
-            match e {
-                VersionEdit::NewFile4(meta) => {
-                    files.insert(meta.file_number, meta.clone());
-                    total_size += meta.file_size;
+        let kind = classifier.classify(&record).kind;
+        let group = replay.push(record);
+        // Names and comparators only count once Replay applied the record,
+        // an atomic group it later drops must not rename anything
+        for applied in replay.applied() {
+            let column_family = record_column_family(&applied.edits);
+            for e in &applied.edits {
+                match e {
+                    VersionEdit::Comparator(name) => decoders.set_comparator(column_family, name),
+                    VersionEdit::ColumnFamilyAdd(name) => {
+                        decoders.set_column_family_name(column_family, name)
+                    }
+                    _ => {}
                 }
-                VersionEdit::DeletedFile(_level, file_number) => {
-                    let file = files.get_mut(&file_number);
-                    match file {
-                        Some(meta) => {
-                            meta.deleted = true;
-                            total_size -= meta.file_size;
-                        }
-                        None => {
-                            println!("File {} not found for deletion", file_number);
-                        }
+            }
+            names.apply(applied);
+        }
+        let record = replay.records.last().unwrap();
+        let keys = decoders.for_column_family(record_column_family(&record.edits));
+        if dump && !json {
+            for e in &record.edits {
+                match e {
+                    VersionEdit::ColumnFamily(id) => {
+                        writeln!(out, "  ColumnFamily: {}", names.label(*id))?
                     }
+                    VersionEdit::NewFile4(meta) => writeln!(
+                        out,
+                        "  {} {{\n{}}}",
+                        meta.format,
+                        meta.labeled(keys, names.label(meta.column_family))
+                    )?,
+                    _ => writeln!(out, "  {}", e.with_keys(keys))?,
                 }
-                _ => {}
+                if let VersionEdit::Comparator(_) = e {
+                    writeln!(out, "  (keys decoded as {})", keys.name())?;
+                }
+            }
+        }
+        if dump && !json {
+            writeln!(out, "  (record kind {})", kind)?;
+        }
+        if json {
+            if dump {
+                let record = json::record_to_json(record, kind, group, &names, keys);
+                writeln!(out, "{}", record)?;
+            }
+            warnings.append(&mut replay.warnings);
+            warnings.append(&mut replay.live.warnings);
+        } else if dump {
+            for warning in replay.warnings.drain(..) {
+                writeln!(out, "Warning: {}", warning)?;
+            }
+            if let Some(group) = group {
+                writeln!(
+                    out,
+                    "Atomic group #{}: record {} of {}",
+                    group.group, group.position, group.size
+                )?;
+            }
+            for warning in replay.live.warnings.drain(..) {
+                writeln!(out, "{}", warning)?;
+            }
+            writeln!(out, "New total size: {}", replay.live.total_size)?;
+            if !replay.live.blob_files.is_empty() {
+                writeln!(out, "New total blob size: {}", replay.live.total_blob_size)?;
             }
         }
-        println!("New total size: {}", total_size);
-        all_edits.push(edit);
     }
-
-    // Now print out the list of files:
-    println!("============================================");
-    println!("List of data files:");
-    let mut v: Vec<FileMetaData> = Vec::with_capacity(files.len());
-    for (_nr, meta) in &files {
-        v.push(meta.clone());
-    }
-    v.sort_by(|a, b| a.file_number.cmp(&b.file_number));
-    for (i, meta) in v.iter().enumerate() {
-        println!("File #{}: {}", i, meta);
+    replay.finish();
+    if json || verify {
+        warnings.append(&mut replay.warnings);
+        warnings.append(&mut replay.live.warnings);
+    } else if lineage {
+        for warning in replay.warnings.drain(..) {
+            eprintln!("Warning: {}", warning);
+        }
+    } else {
+        for warning in replay.warnings.drain(..) {
+            writeln!(out, "============================================")?;
+            writeln!(out, "Warning: {}", warning)?;
+        }
     }
-    println!("============================================");
-    println!("List of alive files:");
-    for (i, meta) in v.iter().enumerate() {
-        if !meta.deleted {
-            println!("Alive file #{}: {}", i, meta);
+    if verify {
+        problems.extend(warnings);
+        problems.extend(replay.verify());
+        writeln!(out, "============================================")?;
+        writeln!(out, "Verified {} records", replay.records.len())?;
+        for problem in &problems {
+            writeln!(out, "Problem: {}", problem)?;
+        }
+        if problems.is_empty() {
+            writeln!(out, "No problems found")?;
+            return Ok(true);
         }
+        writeln!(out, "{} problems found", problems.len())?;
+        return Ok(false);
     }
-    println!("============================================");
-    println!("List of interesting files:");
-    for (i, meta) in v.iter().enumerate() {
-        if meta.file_size < 2000 && meta.level == 0 {
-            println!("Interesting file #{}: {}", i, meta);
+    let Replay {
+        records,
+        live,
+        versions,
+        ..
+    } = replay;
+    if command == Command::Check {
+        let dir = match &options.db_dir {
+            Some(dir) => dir.clone(),
+            None => match manifest_path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            },
+        };
+        let discrepancies = check_directory(&dir, &live, &versions)?;
+        writeln!(out, "============================================")?;
+        writeln!(out, "Checked {} against the manifest", dir.display())?;
+        for discrepancy in &discrepancies {
+            writeln!(out, "{}", discrepancy)?;
+        }
+        if discrepancies.is_empty() {
+            writeln!(out, "No differences found")?;
+            return Ok(true);
         }
+        writeln!(out, "{} differences found", discrepancies.len())?;
+        return Ok(false);
     }
-    // Find and print compactions:
-    let compactions = find_compactions(&all_edits);
-    println!("============================================");
-    println!("\nFound {} potential compactions:", compactions.len());
-    for (i, compaction) in compactions.iter().enumerate() {
-        println!("\nCompaction #{}", i + 1);
-        println!("{}", compaction);
+    if json {
+        if !command.shows(Command::Summary) {
+            return Ok(true);
+        }
+        warnings.extend(versions.warnings.iter().cloned());
+        let summary = json::summary_to_json(
+            &records, stopped_at, warnings, &live, &versions, &names, &decoders,
+        );
+        writeln!(out, "{}", summary)?;
+        return Ok(true);
     }
-    // Print interesting compactions (column family 10, only deletes, only
-    // small files < 2000):
-    for (i, compaction) in compactions.iter().enumerate() {
-        if compaction.column_family == 10 && compaction.only_deletes == true {
-            let mut all_small = true;
-            for d in compaction.deleted_files.iter() {
-                let file = files.get(&d.1);
-                if let Some(f) = file {
-                    if f.file_size > 2000 {
-                        all_small = true;
-                        break;
-                    }
-                }
-            }
-            if all_small {
-                println!("Interesting delete #{}: {}", i, compaction);
+    if command.shows(Command::Files) {
+        print_files(out, &live, &names, &decoders, options.filter.as_ref())?;
+    }
+    if command.shows(Command::Alive) {
+        print_alive(out, &live, &names, &decoders, options.filter.as_ref())?;
+    }
+    if command.shows(Command::Summary) {
+        print_summary(out, &records, &live, &versions, &names, &decoders)?;
+    }
+    if command.shows(Command::Shape) {
+        print_shape(out, &versions, &decoders)?;
+    }
+    if command.shows(Command::Classes) {
+        print_classes(out, &records, &live, &names, options.filter.as_ref())?;
+    }
+    if command.shows(Command::Compactions) {
+        print_compactions(
+            out,
+            &records,
+            &live,
+            &names,
+            &decoders,
+            options.filter.as_ref(),
+        )?;
+    }
+    if command.shows(Command::Stats) {
+        print_stats(out, &records, &names, options.window)?;
+    }
+    if command.shows(Command::Deletions) {
+        print_deletions(
+            out,
+            &records,
+            &versions,
+            &names,
+            &decoders,
+            options.filter.as_ref(),
+            options.thresholds,
+        )?;
+    }
+    if lineage {
+        let records = classify_records(&records);
+        let graph = Lineage::build(&records, options.filter.as_ref());
+        write!(out, "{}", graph.render(options.graph, &names))?;
+    }
+    if let Some(file_number) = options.file_number {
+        let history = FileHistory::build(&classify_records(&records), file_number, &names);
+        writeln!(out, "============================================")?;
+        writeln!(out, "{}", history)?;
+    }
+    Ok(true)
+}
+
+// The manifests to read: the given file, or for a database directory the
+// one CURRENT names, or with --all-manifests all of them. Warns about
+// other manifests in the directory, which are stale leftovers or the
+// remains of a torn CURRENT update.
+fn manifests_to_read(options: &Options) -> io::Result<Vec<(PathBuf, bool)>> {
+    let path = Path::new(&options.manifest_path);
+    if !path.is_dir() {
+        return Ok(vec![(path.to_path_buf(), true)]);
+    }
+    let all = manifest_files(path)?;
+    let current = match current_manifest(path) {
+        Ok(current) => Some(current),
+        Err(e) if options.all_manifests => {
+            eprintln!("Warning: {}", e);
+            None
+        }
+        Err(e) => return Err(e),
+    };
+    let others: Vec<String> = all
+        .iter()
+        .filter(|m| Some(*m) != current.as_ref())
+        .filter_map(|m| m.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect();
+    if !others.is_empty() {
+        eprintln!(
+            "Warning: {} has other manifests than the current one: {}",
+            path.display(),
+            others.join(", ")
+        );
+    }
+    if !options.all_manifests {
+        return Ok(current.into_iter().map(|m| (m, true)).collect());
+    }
+    Ok(all
+        .into_iter()
+        .map(|m| {
+            let is_current = Some(&m) == current.as_ref();
+            (m, is_current)
+        })
+        .collect())
+}
+
+// Reads all manifests to read. Returns false if verify or check found
+// problems in any of them.
+fn run_all(out: &mut impl Write, options: &Options) -> io::Result<bool> {
+    let manifests = manifests_to_read(options)?;
+    let mut ok = true;
+    for (manifest, current) in &manifests {
+        if manifests.len() > 1 {
+            let state = if *current { "current" } else { "not current" };
+            if options.json {
+                let header = json::manifest_header_to_json(manifest, *current);
+                writeln!(out, "{}", header)?;
+            } else if options.command == Command::Lineage {
+                eprintln!("Manifest {} ({})", manifest.display(), state);
+            } else {
+                writeln!(out, "############################################")?;
+                writeln!(out, "Manifest {} ({})", manifest.display(), state)?;
             }
         }
+        ok &= run(out, options, manifest)?;
     }
-    Ok(())
+    Ok(ok)
 }
 
-// Add this to your main function:
+fn main() -> io::Result<()> {
+    let options = parse_args();
+    match run_all(&mut io::stdout().lock(), &options) {
+        Ok(true) => Ok(()),
+        Ok(false) => std::process::exit(1),
+        // The reader is gone, e.g. head got enough lines
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,1028 @@
+//! Reading the log-structured records of a manifest file.
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use crc32c::crc32c;
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek};
+use std::path::Path;
+
+use crate::edit::{
+    record_column_family, BlobFileAddition, BlobFileGarbage, FileMetaData, InternalKey,
+    NewFileFormat, VersionEdit,
+};
+
+#[allow(dead_code)]
+const ZERO_TYPE: u8 = 0;
+const FULL_TYPE: u8 = 1;
+const FIRST_TYPE: u8 = 2;
+const MIDDLE_TYPE: u8 = 3;
+const LAST_TYPE: u8 = 4;
+
+const BLOCK_SIZE: u64 = 0x8000;
+const HEADER_SIZE: u64 = 7;
+
+#[derive(Debug)]
+enum Tag {
+    Comparator = 1,
+    LogNumber = 2,
+    NextFileNumber = 3,
+    LastSequence = 4,
+    CompactCursor = 5,
+    DeletedFile = 6,
+    NewFile = 7,
+    PrevLogNumber = 9,
+    MinLogNumberToKeep = 10,
+    // RocksDB-specific formats
+    NewFile2 = 100,
+    NewFile3 = 102,
+    /// Latest format for adding files.
+    NewFile4 = 103,
+    ColumnFamily = 200,
+    ColumnFamilyAdd = 201,
+    ColumnFamilyDrop = 202,
+    MaxColumnFamily = 203,
+    InAtomicGroup = 300,
+    // BlobDB
+    BlobFileAddition = 400,
+    BlobFileGarbage = 401,
+    // Forward compatible tags have TAG_SAFE_IGNORE_MASK set and are
+    // followed by a length-prefixed payload (except the first WAL tags):
+    DbId = 8193,
+    WalAddition = 8196,
+    WalDeletion = 8197,
+    FullHistoryTsLow = 8198,
+    WalAddition2 = 8199,
+    WalDeletion2 = 8200,
+    PersistUserDefinedTimestamps = 8201,
+    // ... other tags can be added as needed
+}
+
+// Tags from the future with this bit set can be skipped safely.
+const TAG_SAFE_IGNORE_MASK: u32 = 1 << 13;
+
+impl TryFrom<u32> for Tag {
+    type Error = &'static str;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Tag::Comparator),
+            2 => Ok(Tag::LogNumber),
+            3 => Ok(Tag::NextFileNumber),
+            4 => Ok(Tag::LastSequence),
+            5 => Ok(Tag::CompactCursor),
+            6 => Ok(Tag::DeletedFile),
+            7 => Ok(Tag::NewFile),
+            9 => Ok(Tag::PrevLogNumber),
+            10 => Ok(Tag::MinLogNumberToKeep),
+            100 => Ok(Tag::NewFile2),
+            102 => Ok(Tag::NewFile3),
+            103 => Ok(Tag::NewFile4),
+            200 => Ok(Tag::ColumnFamily),
+            201 => Ok(Tag::ColumnFamilyAdd),
+            202 => Ok(Tag::ColumnFamilyDrop),
+            203 => Ok(Tag::MaxColumnFamily),
+            300 => Ok(Tag::InAtomicGroup),
+            400 => Ok(Tag::BlobFileAddition),
+            401 => Ok(Tag::BlobFileGarbage),
+            8193 => Ok(Tag::DbId),
+            8196 => Ok(Tag::WalAddition),
+            8197 => Ok(Tag::WalDeletion),
+            8198 => Ok(Tag::FullHistoryTsLow),
+            8199 => Ok(Tag::WalAddition2),
+            8200 => Ok(Tag::WalDeletion2),
+            8201 => Ok(Tag::PersistUserDefinedTimestamps),
+            _ => Err("Invalid tag value"),
+        }
+    }
+}
+
+impl From<Tag> for u32 {
+    fn from(tag: Tag) -> u32 {
+        tag as u32
+    }
+}
+
+enum NewFileCustomTag {
+    Terminate = 1,
+    NeedCompaction = 2,
+    MinLogNumberToKeepHack = 3,
+    OldestBlobFileNumber = 4,
+    OldestAncesterTime = 5,
+    FileCreationTime = 6,
+    FileChecksum = 7,
+    FileChecksumFuncName = 8,
+    Temperature = 9,
+    MinTimestamp = 10,
+    MaxTimestamp = 11,
+    UniqueId = 12,
+    EpochNumber = 13,
+    CompensateRangeDeletionSize = 14,
+    TailSize = 15,
+    UserDefinedTimestampsPersisted = 16,
+}
+
+impl TryFrom<u32> for NewFileCustomTag {
+    type Error = &'static str;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(NewFileCustomTag::Terminate),
+            2 => Ok(NewFileCustomTag::NeedCompaction),
+            3 => Ok(NewFileCustomTag::MinLogNumberToKeepHack),
+            4 => Ok(NewFileCustomTag::OldestBlobFileNumber),
+            5 => Ok(NewFileCustomTag::OldestAncesterTime),
+            6 => Ok(NewFileCustomTag::FileCreationTime),
+            7 => Ok(NewFileCustomTag::FileChecksum),
+            8 => Ok(NewFileCustomTag::FileChecksumFuncName),
+            9 => Ok(NewFileCustomTag::Temperature),
+            10 => Ok(NewFileCustomTag::MinTimestamp),
+            11 => Ok(NewFileCustomTag::MaxTimestamp),
+            12 => Ok(NewFileCustomTag::UniqueId),
+            13 => Ok(NewFileCustomTag::EpochNumber),
+            14 => Ok(NewFileCustomTag::CompensateRangeDeletionSize),
+            15 => Ok(NewFileCustomTag::TailSize),
+            16 => Ok(NewFileCustomTag::UserDefinedTimestampsPersisted),
+            _ => Err("Invalid NewFileCustomTag value"),
+        }
+    }
+}
+
+impl From<NewFileCustomTag> for u32 {
+    fn from(tag: NewFileCustomTag) -> u32 {
+        tag as u32
+    }
+}
+
+// Custom field tags of blob file additions and garbage records:
+const BLOB_CUSTOM_END_MARKER: u32 = 0;
+const BLOB_CUSTOM_FORWARD_INCOMPATIBLE_MASK: u32 = 1 << 6;
+
+// Tags within a WAL addition:
+const WAL_ADDITION_TERMINATE: u32 = 1;
+const WAL_ADDITION_SYNCED_SIZE: u32 = 2;
+
+/// One record of the manifest together with where it was found. A record
+/// holds the edits RocksDB wrote as one unit, normally for one column
+/// family.
+#[derive(Debug)]
+pub struct Record {
+    /// Counted from 0.
+    pub index: usize,
+    /// Start of the record in the file.
+    pub offset: u64,
+    /// Bytes including headers and block padding.
+    pub length: u64,
+    /// The decoded edits.
+    pub edits: Vec<VersionEdit>,
+}
+
+// A record for tests of the modules working on records, laid out as if
+// every record took 0x100 bytes
+#[cfg(test)]
+pub(crate) fn record(index: usize, edits: Vec<VersionEdit>) -> Record {
+    Record {
+        index,
+        offset: index as u64 * 0x100,
+        length: 0x100,
+        edits,
+    }
+}
+
+/// Reads the records of a manifest. Manifests use the RocksDB log format:
+/// 32KiB blocks of checksummed fragments which are joined into records.
+pub struct ManifestReader<R = BufReader<File>> {
+    reader: R,
+    records_read: usize,
+    warnings: Vec<String>, // not yet taken
+}
+
+fn read_varint32(cursor: &mut Cursor<Vec<u8>>) -> io::Result<u32> {
+    let mut result: u32 = 0;
+    let mut shift = 0;
+    loop {
+        let mut buf = [0u8; 1];
+        cursor.read_exact(&mut buf)?;
+        let byte = buf[0] as u32;
+        result |= (byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    Ok(result)
+}
+
+fn read_varint64(cursor: &mut Cursor<Vec<u8>>) -> io::Result<u64> {
+    let mut result: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let mut buf = [0u8; 1];
+        cursor.read_exact(&mut buf)?;
+        let byte = buf[0] as u64;
+        result |= (byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    Ok(result)
+}
+
+fn read_length_prefixed_slice(cursor: &mut Cursor<Vec<u8>>) -> io::Result<Vec<u8>> {
+    let length = read_varint32(cursor)? as usize;
+    let mut data = vec![0u8; length];
+    cursor.read_exact(&mut data)?;
+    Ok(data)
+}
+
+// Skips the custom fields of a blob file addition or garbage record up to
+// the end marker, refusing fields which must not be ignored.
+fn skip_blob_custom_fields(cursor: &mut Cursor<Vec<u8>>) -> io::Result<()> {
+    loop {
+        let custom_tag = read_varint32(cursor)?;
+        if custom_tag == BLOB_CUSTOM_END_MARKER {
+            return Ok(());
+        }
+        if (custom_tag & BLOB_CUSTOM_FORWARD_INCOMPATIBLE_MASK) != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("blob file custom field not supported: {}", custom_tag),
+            ));
+        }
+        read_length_prefixed_slice(cursor)?;
+    }
+}
+
+fn read_wal_addition(cursor: &mut Cursor<Vec<u8>>) -> io::Result<VersionEdit> {
+    let log_number = read_varint64(cursor)?;
+    let mut synced_size = None;
+    loop {
+        match read_varint32(cursor)? {
+            WAL_ADDITION_TERMINATE => break,
+            WAL_ADDITION_SYNCED_SIZE => synced_size = Some(read_varint64(cursor)?),
+            wal_tag => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unknown WAL addition tag: {}", wal_tag),
+                ));
+            }
+        }
+    }
+    Ok(VersionEdit::WalAddition(log_number, synced_size))
+}
+
+fn unmask_crc(c: u32) -> u32 {
+    let rot = c.wrapping_sub(0xa282ead8u32);
+    rot.rotate_right(17)
+}
+
+impl ManifestReader {
+    /// Opens the manifest file at `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(ManifestReader::from_reader(BufReader::new(file)))
+    }
+}
+
+impl<R: Read + Seek> ManifestReader<R> {
+    /// Reads a manifest from any seekable source, e.g. a copy in memory.
+    pub fn from_reader(reader: R) -> Self {
+        ManifestReader {
+            reader,
+            records_read: 0,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Current offset in the file, the start of the next record.
+    pub fn position(&mut self) -> io::Result<u64> {
+        self.reader.stream_position()
+    }
+
+    /// Problems found while reading, like checksum mismatches, which did
+    /// not prevent decoding the records. Each is only returned once.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Reads the next record with its index and location, or None at the
+    /// end of the manifest.
+    pub fn next_record(&mut self) -> io::Result<Option<Record>> {
+        let offset = self.position()?;
+        let Some(edits) = self.read_record()? else {
+            return Ok(None);
+        };
+        let record = Record {
+            index: self.records_read,
+            offset,
+            length: self.position()? - offset,
+            edits,
+        };
+        self.records_read += 1;
+        Ok(Some(record))
+    }
+
+    /// Reads and decodes the edits of the next record, or returns None at
+    /// the end of the manifest.
+    pub fn read_record(&mut self) -> io::Result<Option<Vec<VersionEdit>>> {
+        let mut whole_payload: Vec<u8> = Vec::new();
+        loop {
+            let mut left_in_block = BLOCK_SIZE - (self.position()? % BLOCK_SIZE);
+            if left_in_block < HEADER_SIZE {
+                let mut buf = vec![0u8; left_in_block as usize];
+                let _ = self.reader.read_exact(&mut buf);
+                left_in_block = BLOCK_SIZE;
+            }
+
+            // Read the 7-byte header
+            let mut header = [0u8; 7]; // 4 (crc) + 2 (size) + 1 (type)
+            match self.reader.read_exact(&mut header) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e),
+            }
+
+            // Parse header
+            let mut expected_crc = (&header[0..4]).read_u32::<LittleEndian>()?;
+            let size = (&header[4..6]).read_u16::<LittleEndian>()? as usize;
+            let record_type = header[6]; // Should be 1
+
+            // All zero?
+            if expected_crc == 0 && size == 0 && record_type == 0 {
+                let mut buf = vec![0u8; left_in_block as usize];
+                let _ = self.reader.read_exact(&mut buf);
+            }
+
+            expected_crc = unmask_crc(expected_crc);
+            // Read the payload
+            let mut payload = vec![0u8; size];
+            self.reader.read_exact(&mut payload)?;
+
+            // Verify CRC
+            // Create data for CRC calculation: type byte + payload
+            let mut data_for_crc = Vec::with_capacity(1 + size);
+            data_for_crc.push(record_type); // The type byte
+            data_for_crc.extend_from_slice(&payload);
+            let actual_crc = crc32c(&data_for_crc);
+
+            if actual_crc != expected_crc {
+                let offset = self.position()?;
+                self.warnings.push(format!(
+                    "CRC mismatch: expected {:x}, got {:x}, current offset in file: {}, size of last payload: {}",
+                    expected_crc, actual_crc, offset, size,
+                ));
+            }
+            match record_type {
+                FULL_TYPE => {
+                    whole_payload = payload;
+                    break;
+                }
+                FIRST_TYPE => {
+                    whole_payload = payload;
+                }
+                MIDDLE_TYPE => {
+                    whole_payload.extend_from_slice(&payload);
+                }
+                LAST_TYPE => {
+                    whole_payload.extend_from_slice(&payload);
+                    break;
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unexpected record type: {}", record_type),
+                    ));
+                }
+            }
+        }
+
+        // Create a cursor to read from the payload
+        let size = whole_payload.len();
+        let mut cursor = std::io::Cursor::new(whole_payload);
+        let mut edits = Vec::new();
+
+        // Read all items from the payload
+        while cursor.position() < size as u64 {
+            let tag = read_varint32(&mut cursor)?;
+            match Tag::try_from(tag) {
+                Ok(Tag::Comparator) => {
+                    // kComparator
+                    let data = read_length_prefixed_slice(&mut cursor)?;
+                    let comparator = String::from_utf8(data)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    edits.push(VersionEdit::Comparator(comparator));
+                }
+                Ok(Tag::LogNumber) => {
+                    // kLogNumber
+                    let log_number = read_varint64(&mut cursor)?;
+                    edits.push(VersionEdit::LogNumber(log_number));
+                }
+                Ok(Tag::NextFileNumber) => {
+                    // kNextFileNumber
+                    let next_file_number = read_varint64(&mut cursor)?;
+                    edits.push(VersionEdit::NextFileNumber(next_file_number));
+                }
+                Ok(Tag::LastSequence) => {
+                    // kLastSequence
+                    let last_sequence = read_varint64(&mut cursor)?;
+                    edits.push(VersionEdit::LastSequence(last_sequence));
+                }
+                Ok(Tag::NewFile) | Ok(Tag::NewFile2) | Ok(Tag::NewFile3) => {
+                    // kNewFile, kNewFile2, kNewFile3: legacy formats without
+                    // custom fields, kNewFile2 adds the seqnos and kNewFile3
+                    // additionally the path id
+                    let format = match Tag::try_from(tag) {
+                        Ok(Tag::NewFile) => NewFileFormat::NewFile,
+                        Ok(Tag::NewFile2) => NewFileFormat::NewFile2,
+                        _ => NewFileFormat::NewFile3,
+                    };
+                    let level = read_varint32(&mut cursor)?;
+                    let file_number = read_varint64(&mut cursor)?;
+                    let path_id = if format == NewFileFormat::NewFile3 {
+                        read_varint32(&mut cursor)?
+                    } else {
+                        0
+                    };
+                    let file_size = read_varint64(&mut cursor)?;
+
+                    let smallest_key_data = read_length_prefixed_slice(&mut cursor)?;
+                    let largest_key_data = read_length_prefixed_slice(&mut cursor)?;
+
+                    let (smallest_seqno, largest_seqno) = if format == NewFileFormat::NewFile {
+                        (0, 0)
+                    } else {
+                        (read_varint64(&mut cursor)?, read_varint64(&mut cursor)?)
+                    };
+
+                    edits.push(VersionEdit::NewFile4(FileMetaData {
+                        format,
+                        level,
+                        file_number,
+                        file_size,
+                        smallest_key: InternalKey::decode(smallest_key_data),
+                        largest_key: InternalKey::decode(largest_key_data),
+                        smallest_seqno,
+                        largest_seqno,
+                        path_id,
+                        ..Default::default()
+                    }));
+                }
+                Ok(Tag::NewFile4) => {
+                    // kNewFile4
+                    let level = read_varint32(&mut cursor)?;
+                    let file_number = read_varint64(&mut cursor)?;
+                    let file_size = read_varint64(&mut cursor)?;
+
+                    let smallest_key_data = read_length_prefixed_slice(&mut cursor)?;
+                    let largest_key_data = read_length_prefixed_slice(&mut cursor)?;
+
+                    let smallest_seqno = read_varint64(&mut cursor)?;
+                    let largest_seqno = read_varint64(&mut cursor)?;
+
+                    let mut meta = FileMetaData {
+                        level,
+                        file_number,
+                        file_size,
+                        smallest_key: InternalKey::decode(smallest_key_data),
+                        largest_key: InternalKey::decode(largest_key_data),
+                        smallest_seqno,
+                        largest_seqno,
+                        ..Default::default()
+                    };
+
+                    // Read custom fields until terminating tag
+                    loop {
+                        let custom_tag = read_varint32(&mut cursor)?;
+                        if custom_tag == NewFileCustomTag::Terminate as u32 {
+                            break;
+                        }
+
+                        let field_data = read_length_prefixed_slice(&mut cursor)?;
+                        match NewFileCustomTag::try_from(custom_tag) {
+                            Ok(NewFileCustomTag::Terminate) => {
+                                // kTerminate
+                                break;
+                            }
+                            Ok(NewFileCustomTag::NeedCompaction) => {
+                                // kNeedCompaction
+                                if field_data.len() != 1 {
+                                    return Err(io::Error::new(
+                                        io::ErrorKind::InvalidData,
+                                        "need_compaction field wrong size",
+                                    ));
+                                }
+                                meta.needs_compaction = field_data[0] == 1;
+                            }
+                            Ok(NewFileCustomTag::MinLogNumberToKeepHack) => {
+                                // kMinLogNumberToKeepHack
+                                let mut field_cursor = Cursor::new(field_data);
+                                meta.min_log_number_to_keep =
+                                    Some(field_cursor.read_u64::<LittleEndian>()?);
+                            }
+                            Ok(NewFileCustomTag::OldestBlobFileNumber) => {
+                                // kOldestBlobFileNumber
+                                let mut field_cursor = Cursor::new(field_data);
+                                meta.oldest_blob_file_number =
+                                    Some(read_varint64(&mut field_cursor)?);
+                            }
+                            Ok(NewFileCustomTag::OldestAncesterTime) => {
+                                // kOldestAncesterTime
+                                let mut field_cursor = Cursor::new(field_data);
+                                meta.oldest_ancester_time = read_varint64(&mut field_cursor)?;
+                            }
+                            Ok(NewFileCustomTag::FileCreationTime) => {
+                                // kFileCreationTime
+                                let mut field_cursor = Cursor::new(field_data);
+                                meta.file_creation_time = read_varint64(&mut field_cursor)?;
+                            }
+                            Ok(NewFileCustomTag::FileChecksum) => {
+                                // kFileChecksum
+                                meta.file_checksum = String::from_utf8(field_data)
+                                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                            }
+                            Ok(NewFileCustomTag::FileChecksumFuncName) => {
+                                // kFileChecksumFuncName
+                                meta.file_checksum_func_name = String::from_utf8(field_data)
+                                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                            }
+                            Ok(NewFileCustomTag::Temperature) => {
+                                // kTemperature
+                                if field_data.len() != 1 {
+                                    return Err(io::Error::new(
+                                        io::ErrorKind::InvalidData,
+                                        "temperature field wrong size",
+                                    ));
+                                }
+                                meta.temperature = Some(field_data[0]);
+                            }
+                            Ok(NewFileCustomTag::UniqueId) => {
+                                // kUniqueId
+                                meta.unique_id = field_data;
+                            }
+                            Ok(NewFileCustomTag::EpochNumber) => {
+                                // kEpochNumber
+                                let mut field_cursor = Cursor::new(field_data);
+                                meta.epoch_number = read_varint64(&mut field_cursor)?;
+                            }
+                            Ok(NewFileCustomTag::CompensateRangeDeletionSize) => {
+                                // kCompensatedRangeDeletionSize
+                                let mut field_cursor = Cursor::new(field_data);
+                                meta.compensated_range_deletion_size =
+                                    read_varint64(&mut field_cursor)?;
+                            }
+                            Ok(NewFileCustomTag::TailSize) => {
+                                // kTailSize
+                                let mut field_cursor = Cursor::new(field_data);
+                                meta.tail_size = read_varint64(&mut field_cursor)?;
+                            }
+                            Ok(NewFileCustomTag::UserDefinedTimestampsPersisted) => {
+                                // kUserDefinedTimestampsPersisted
+                                if field_data.len() != 1 {
+                                    return Err(io::Error::new(
+                                        io::ErrorKind::InvalidData,
+                                        "user-defined timestamps persisted field wrong size",
+                                    ));
+                                }
+                                meta.user_defined_timestamps_persisted = field_data[0] == 1;
+                            }
+                            Ok(NewFileCustomTag::MinTimestamp) => {
+                                meta.min_timestamp = Some(field_data);
+                            }
+                            Ok(NewFileCustomTag::MaxTimestamp) => {
+                                meta.max_timestamp = Some(field_data);
+                            }
+                            Err(err) => {
+                                if (custom_tag & 0x40) != 0 {
+                                    // kCustomTagNonSafeIgnoreMask
+                                    return Err(io::Error::new(
+                                        io::ErrorKind::InvalidData,
+                                        format!(
+                                            "new-file4 custom field not supported: {} {}",
+                                            custom_tag, err
+                                        ),
+                                    ));
+                                }
+                                // Safe to ignore this tag
+                            }
+                        }
+                    }
+                    edits.push(VersionEdit::NewFile4(meta));
+                }
+                Ok(Tag::ColumnFamily) => {
+                    // kColumnFamily
+                    let column_family = read_varint32(&mut cursor)?;
+                    edits.push(VersionEdit::ColumnFamily(column_family));
+                }
+                Ok(Tag::ColumnFamilyAdd) => {
+                    // kColumnFamilyAdd
+                    let data = read_length_prefixed_slice(&mut cursor)?;
+                    let column_family_name = String::from_utf8(data)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    edits.push(VersionEdit::ColumnFamilyAdd(column_family_name));
+                }
+                Ok(Tag::PrevLogNumber) => {
+                    // kPrevLogNumber
+                    let prev_log_number = read_varint64(&mut cursor)?;
+                    edits.push(VersionEdit::PrevLogNumber(prev_log_number));
+                }
+                Ok(Tag::MaxColumnFamily) => {
+                    // kMaxColumnFamily
+                    let max_column_family = read_varint32(&mut cursor)?;
+                    edits.push(VersionEdit::MaxColumnFamily(max_column_family));
+                }
+                Ok(Tag::DeletedFile) => {
+                    // kDeletedFile
+                    let level = read_varint32(&mut cursor)?;
+                    let file_number = read_varint64(&mut cursor)?;
+                    edits.push(VersionEdit::DeletedFile(level, file_number));
+                }
+                Ok(Tag::CompactCursor) => {
+                    // kCompactCursor
+                    let level = read_varint32(&mut cursor)?;
+                    let cursor_data = read_length_prefixed_slice(&mut cursor)?;
+                    edits.push(VersionEdit::CompactCursor(
+                        level,
+                        InternalKey::decode(cursor_data),
+                    ));
+                }
+                Ok(Tag::MinLogNumberToKeep) => {
+                    // kMinLogNumberToKeep
+                    let min_log_number = read_varint64(&mut cursor)?;
+                    edits.push(VersionEdit::MinLogNumberToKeep(min_log_number));
+                }
+                Ok(Tag::ColumnFamilyDrop) => {
+                    // kColumnFamilyDrop
+                    edits.push(VersionEdit::ColumnFamilyDrop);
+                }
+                Ok(Tag::InAtomicGroup) => {
+                    // kInAtomicGroup
+                    let remaining_entries = read_varint32(&mut cursor)?;
+                    edits.push(VersionEdit::InAtomicGroup(remaining_entries));
+                }
+                Ok(Tag::BlobFileAddition) => {
+                    // kBlobFileAddition
+                    let blob_file_number = read_varint64(&mut cursor)?;
+                    let total_blob_count = read_varint64(&mut cursor)?;
+                    let total_blob_bytes = read_varint64(&mut cursor)?;
+                    let method_data = read_length_prefixed_slice(&mut cursor)?;
+                    let checksum_method = String::from_utf8(method_data)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    let checksum_value = read_length_prefixed_slice(&mut cursor)?;
+                    skip_blob_custom_fields(&mut cursor)?;
+                    edits.push(VersionEdit::BlobFileAddition(BlobFileAddition {
+                        blob_file_number,
+                        total_blob_count,
+                        total_blob_bytes,
+                        checksum_method,
+                        checksum_value,
+                    }));
+                }
+                Ok(Tag::BlobFileGarbage) => {
+                    // kBlobFileGarbage
+                    let blob_file_number = read_varint64(&mut cursor)?;
+                    let garbage_blob_count = read_varint64(&mut cursor)?;
+                    let garbage_blob_bytes = read_varint64(&mut cursor)?;
+                    skip_blob_custom_fields(&mut cursor)?;
+                    edits.push(VersionEdit::BlobFileGarbage(BlobFileGarbage {
+                        blob_file_number,
+                        garbage_blob_count,
+                        garbage_blob_bytes,
+                    }));
+                }
+                Ok(Tag::DbId) => {
+                    // kDbId
+                    let data = read_length_prefixed_slice(&mut cursor)?;
+                    let db_id = String::from_utf8(data)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    edits.push(VersionEdit::DbId(db_id));
+                }
+                Ok(Tag::FullHistoryTsLow) => {
+                    // kFullHistoryTsLow
+                    let ts_low = read_length_prefixed_slice(&mut cursor)?;
+                    edits.push(VersionEdit::FullHistoryTsLow(ts_low));
+                }
+                Ok(Tag::PersistUserDefinedTimestamps) => {
+                    // kPersistUserDefinedTimestamps
+                    let data = read_length_prefixed_slice(&mut cursor)?;
+                    if data.len() != 1 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "persist_user_defined_timestamps field wrong size",
+                        ));
+                    }
+                    edits.push(VersionEdit::PersistUserDefinedTimestamps(data[0] == 1));
+                }
+                Ok(Tag::WalAddition) => {
+                    // kWalAddition
+                    edits.push(read_wal_addition(&mut cursor)?);
+                }
+                Ok(Tag::WalDeletion) => {
+                    // kWalDeletion
+                    let log_number = read_varint64(&mut cursor)?;
+                    edits.push(VersionEdit::WalDeletion(log_number));
+                }
+                Ok(Tag::WalAddition2) => {
+                    // kWalAddition2
+                    let data = read_length_prefixed_slice(&mut cursor)?;
+                    edits.push(read_wal_addition(&mut Cursor::new(data))?);
+                }
+                Ok(Tag::WalDeletion2) => {
+                    // kWalDeletion2
+                    let data = read_length_prefixed_slice(&mut cursor)?;
+                    let log_number = read_varint64(&mut Cursor::new(data))?;
+                    edits.push(VersionEdit::WalDeletion(log_number));
+                }
+                // ... handle other tags
+                Err(_) if (tag & TAG_SAFE_IGNORE_MASK) != 0 => {
+                    // Tag from the future, skip its payload
+                    read_length_prefixed_slice(&mut cursor)?;
+                }
+                Err(err) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unknown tag: {} {}", tag, err),
+                    ));
+                }
+            }
+        }
+
+        // The column family of a record is only given once, after its files
+        let column_family = record_column_family(&edits);
+        for e in edits.iter_mut() {
+            if let VersionEdit::NewFile4(meta) = e {
+                meta.column_family = column_family;
+            }
+        }
+
+        Ok(Some(edits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::ValueType;
+
+    fn varint(out: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn slice(out: &mut Vec<u8>, data: &[u8]) {
+        varint(out, data.len() as u64);
+        out.extend_from_slice(data);
+    }
+
+    fn key(out: &mut Vec<u8>, user_key: &[u8], sequence: u64) {
+        let mut data = user_key.to_vec();
+        data.extend_from_slice(&(sequence << 8 | 1).to_le_bytes());
+        slice(out, &data);
+    }
+
+    // Reads one record made of a single full fragment with the payload
+    fn read(payload: &[u8]) -> io::Result<Vec<VersionEdit>> {
+        let mut data_for_crc = vec![FULL_TYPE];
+        data_for_crc.extend_from_slice(payload);
+        let masked_crc = crc32c(&data_for_crc)
+            .rotate_left(17)
+            .wrapping_add(0xa282ead8);
+        let mut manifest = masked_crc.to_le_bytes().to_vec();
+        manifest.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        manifest.push(FULL_TYPE);
+        manifest.extend_from_slice(payload);
+        let mut reader = ManifestReader::from_reader(Cursor::new(manifest));
+        let edits = reader.read_record()?.unwrap();
+        assert!(reader.take_warnings().is_empty());
+        assert!(reader.read_record()?.is_none());
+        Ok(edits)
+    }
+
+    fn error(payload: &[u8]) -> String {
+        read(payload).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn blob_file_addition_and_garbage() {
+        let mut payload = Vec::new();
+        varint(&mut payload, 400);
+        for value in [11, 3, 300] {
+            varint(&mut payload, value);
+        }
+        slice(&mut payload, b"crc32c");
+        slice(&mut payload, &[1, 2, 3, 4]);
+        // A custom field which may be ignored, then the end marker
+        varint(&mut payload, 1);
+        slice(&mut payload, b"x");
+        varint(&mut payload, 0);
+        varint(&mut payload, 401);
+        for value in [11, 1, 100, 0] {
+            varint(&mut payload, value);
+        }
+        let edits = read(&payload).unwrap();
+        assert_eq!(edits.len(), 2);
+        let VersionEdit::BlobFileAddition(addition) = &edits[0] else {
+            panic!("{:?}", edits[0]);
+        };
+        assert_eq!(
+            (
+                addition.blob_file_number,
+                addition.total_blob_count,
+                addition.total_blob_bytes
+            ),
+            (11, 3, 300)
+        );
+        assert_eq!(addition.checksum_method, "crc32c");
+        assert_eq!(addition.checksum_value, vec![1, 2, 3, 4]);
+        let VersionEdit::BlobFileGarbage(garbage) = &edits[1] else {
+            panic!("{:?}", edits[1]);
+        };
+        assert_eq!(
+            (
+                garbage.blob_file_number,
+                garbage.garbage_blob_count,
+                garbage.garbage_blob_bytes
+            ),
+            (11, 1, 100)
+        );
+    }
+
+    #[test]
+    fn blob_custom_field_forward_incompatible() {
+        let mut payload = Vec::new();
+        varint(&mut payload, 401);
+        for value in [11, 1, 100] {
+            varint(&mut payload, value);
+        }
+        varint(
+            &mut payload,
+            u64::from(BLOB_CUSTOM_FORWARD_INCOMPATIBLE_MASK) | 1,
+        );
+        slice(&mut payload, b"x");
+        varint(&mut payload, 0);
+        assert_eq!(error(&payload), "blob file custom field not supported: 65");
+    }
+
+    #[test]
+    fn wal_additions_and_deletions() {
+        let mut payload = Vec::new();
+        varint(&mut payload, 8196);
+        for value in [5, 2, 1000, 1] {
+            varint(&mut payload, value);
+        }
+        varint(&mut payload, 8197);
+        varint(&mut payload, 3);
+        varint(&mut payload, 8199);
+        slice(&mut payload, &[7, 1]);
+        varint(&mut payload, 8200);
+        slice(&mut payload, &[4]);
+        let edits = read(&payload).unwrap();
+        assert!(matches!(
+            edits[..],
+            [
+                VersionEdit::WalAddition(5, Some(1000)),
+                VersionEdit::WalDeletion(3),
+                VersionEdit::WalAddition(7, None),
+                VersionEdit::WalDeletion(4),
+            ]
+        ));
+
+        let mut payload = Vec::new();
+        varint(&mut payload, 8199);
+        slice(&mut payload, &[7, 3, 1]);
+        assert_eq!(error(&payload), "Unknown WAL addition tag: 3");
+    }
+
+    #[test]
+    fn forward_compatible_tags() {
+        let mut payload = Vec::new();
+        varint(&mut payload, 8193);
+        slice(&mut payload, b"db-id");
+        varint(&mut payload, 8198);
+        slice(&mut payload, &[0, 1]);
+        varint(&mut payload, 8201);
+        slice(&mut payload, &[1]);
+        // Unknown, but with the safe-ignore bit
+        varint(&mut payload, 8300);
+        slice(&mut payload, b"future");
+        let edits = read(&payload).unwrap();
+        assert_eq!(edits.len(), 3);
+        assert!(matches!(&edits[0], VersionEdit::DbId(id) if id == "db-id"));
+        assert!(matches!(&edits[1], VersionEdit::FullHistoryTsLow(ts) if ts == &[0, 1]));
+        assert!(matches!(
+            edits[2],
+            VersionEdit::PersistUserDefinedTimestamps(true)
+        ));
+
+        let mut payload = Vec::new();
+        varint(&mut payload, 8201);
+        slice(&mut payload, &[1, 0]);
+        assert_eq!(
+            error(&payload),
+            "persist_user_defined_timestamps field wrong size"
+        );
+
+        let mut payload = Vec::new();
+        varint(&mut payload, 500);
+        slice(&mut payload, b"future");
+        assert_eq!(error(&payload), "Unknown tag: 500 Invalid tag value");
+    }
+
+    #[test]
+    fn legacy_new_files() {
+        let mut payload = Vec::new();
+        // kNewFile: level, number, size and keys
+        varint(&mut payload, 7);
+        for value in [1, 8, 100] {
+            varint(&mut payload, value);
+        }
+        key(&mut payload, b"a", 5);
+        key(&mut payload, b"b", 6);
+        // kNewFile2 adds the seqnos
+        varint(&mut payload, 100);
+        for value in [2, 9, 200] {
+            varint(&mut payload, value);
+        }
+        key(&mut payload, b"c", 7);
+        key(&mut payload, b"d", 8);
+        varint(&mut payload, 7);
+        varint(&mut payload, 8);
+        // kNewFile3 the path id after the number
+        varint(&mut payload, 102);
+        for value in [3, 10, 1, 300] {
+            varint(&mut payload, value);
+        }
+        key(&mut payload, b"e", 9);
+        key(&mut payload, b"f", 10);
+        varint(&mut payload, 9);
+        varint(&mut payload, 10);
+        // The column family comes after the files
+        varint(&mut payload, 200);
+        varint(&mut payload, 4);
+        let edits = read(&payload).unwrap();
+        let files: Vec<&FileMetaData> = edits
+            .iter()
+            .filter_map(|e| match e {
+                VersionEdit::NewFile4(meta) => Some(meta),
+                _ => None,
+            })
+            .collect();
+        let summary: Vec<_> = files
+            .iter()
+            .map(|f| {
+                (
+                    f.format,
+                    f.column_family,
+                    f.level,
+                    f.file_number,
+                    f.path_id,
+                    f.file_size,
+                    f.smallest_seqno,
+                    f.largest_seqno,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (NewFileFormat::NewFile, 4, 1, 8, 0, 100, 0, 0),
+                (NewFileFormat::NewFile2, 4, 2, 9, 0, 200, 7, 8),
+                (NewFileFormat::NewFile3, 4, 3, 10, 1, 300, 9, 10),
+            ]
+        );
+        assert_eq!(files[2].smallest_key.user_key, b"e");
+        assert_eq!(files[2].largest_key.sequence, 10);
+        assert_eq!(files[2].largest_key.value_type, ValueType::Value);
+    }
+
+    #[test]
+    fn new_file4_custom_fields() {
+        let new_file4 = |custom_tag: u64| {
+            let mut payload = Vec::new();
+            varint(&mut payload, 103);
+            for value in [0, 12, 400] {
+                varint(&mut payload, value);
+            }
+            key(&mut payload, b"a", 1);
+            key(&mut payload, b"z", 2);
+            varint(&mut payload, 1);
+            varint(&mut payload, 2);
+            varint(&mut payload, custom_tag);
+            slice(&mut payload, &[1]);
+            varint(&mut payload, 1);
+            payload
+        };
+        // kNeedCompaction
+        let edits = read(&new_file4(2)).unwrap();
+        assert!(matches!(&edits[0], VersionEdit::NewFile4(meta) if meta.needs_compaction));
+        // Unknown tags are skipped unless they are forward incompatible
+        let edits = read(&new_file4(20)).unwrap();
+        assert!(matches!(&edits[0], VersionEdit::NewFile4(meta) if meta.file_number == 12));
+        assert_eq!(
+            error(&new_file4(0x40 | 20)),
+            "new-file4 custom field not supported: 84 Invalid NewFileCustomTag value"
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! Applying records in order, the way RocksDB recovery does.
+
+use std::ops::Range;
+
+use crate::edit::{atomic_group_remaining, VersionEdit};
+use crate::reader::Record;
+use crate::version::{LiveFiles, VersionSet};
+
+/// Where a record lies within its atomic group.
+#[derive(Debug, Clone, Copy)]
+pub struct AtomicGroupPosition {
+    /// Counted from 1.
+    pub group: usize,
+    /// Counted from 1.
+    pub position: usize,
+    /// Number of records in the group.
+    pub size: usize,
+}
+
+/// Replays the records of a manifest into the live files and the per
+/// column family versions. Records of an atomic group are only applied
+/// once the group is complete.
+#[derive(Default)]
+pub struct Replay {
+    /// The records applied so far, those of discarded atomic groups removed.
+    pub records: Vec<Record>,
+    /// The files the applied records added and deleted.
+    pub live: LiveFiles,
+    /// The per column family versions.
+    pub versions: VersionSet,
+    /// Number of atomic groups seen.
+    pub atomic_groups: usize,
+    /// Not yet reported.
+    pub warnings: Vec<String>,
+    // Index in records and file offset where the currently open atomic
+    // group started, and the remaining count its next record must carry:
+    pending_group: Option<(usize, u64, u32)>,
+    // Indices in records of those the last push applied:
+    applied: Range<usize>,
+}
+
+impl Replay {
+    /// Applies the next record, or holds it back if it belongs to an
+    /// atomic group which is not complete yet. Returns the position of the
+    /// record in its atomic group, if any.
+    pub fn push(&mut self, record: Record) -> Option<AtomicGroupPosition> {
+        let remaining = atomic_group_remaining(&record.edits);
+        let offset = record.offset;
+        if let Some((start, start_offset, expected)) = self.pending_group {
+            // RocksDB refuses to recover from either case, we just drop the
+            // unfinished group and carry on.
+            match remaining {
+                None => self.drop_pending_group(
+                    start,
+                    start_offset,
+                    "interrupted by a record outside of it",
+                ),
+                Some(remaining) if remaining != expected => self.drop_pending_group(
+                    start,
+                    start_offset,
+                    "interrupted by the start of another group",
+                ),
+                Some(_) => {}
+            }
+        }
+        let index = self.records.len();
+        self.records.push(record);
+        self.applied = index..index;
+
+        let remaining = match remaining {
+            None => {
+                self.apply(index);
+                self.applied = index..index + 1;
+                return None;
+            }
+            Some(remaining) => remaining,
+        };
+        let (start, start_offset) = match self.pending_group {
+            Some((start, start_offset, _)) => (start, start_offset),
+            None => {
+                self.atomic_groups += 1;
+                (index, offset)
+            }
+        };
+        let position = index - start + 1;
+        if remaining == 0 {
+            for i in start..=index {
+                self.apply(i);
+            }
+            self.applied = start..index + 1;
+            self.pending_group = None;
+        } else {
+            self.pending_group = Some((start, start_offset, remaining - 1));
+        }
+        Some(AtomicGroupPosition {
+            group: self.atomic_groups,
+            position,
+            size: position + remaining as usize,
+        })
+    }
+
+    /// The records the last `push` applied: none while an atomic group is
+    /// incomplete, all records of the group once it completes.
+    pub fn applied(&self) -> &[Record] {
+        &self.records[self.applied.clone()]
+    }
+
+    /// Ends the replay. Like RocksDB recovery, an atomic group which was
+    /// never completed is ignored, neither its files nor its compactions
+    /// count, and its records are removed.
+    pub fn finish(&mut self) {
+        if let Some((start, start_offset, _)) = self.pending_group.take() {
+            self.warnings.push(format!(
+                "Incomplete atomic group #{} at end of manifest (offset {:x}, {} records), ignored",
+                self.atomic_groups,
+                start_offset,
+                self.records.len() - start
+            ));
+            self.records.truncate(start);
+        }
+    }
+
+    // Forgets the unfinished atomic group starting at records[start], its
+    // records are removed so that no later report counts them.
+    fn drop_pending_group(&mut self, start: usize, start_offset: u64, reason: &str) {
+        self.warnings.push(format!(
+            "Atomic group #{} starting at {:x} {}, ignoring its {} records",
+            self.atomic_groups,
+            start_offset,
+            reason,
+            self.records.len() - start
+        ));
+        self.records.truncate(start);
+        self.pending_group = None;
+    }
+
+    /// What verify reports: the warnings of the replay, the live files
+    /// and the versions which were not taken yet, then inconsistencies
+    /// RocksDB recovery would fail on, or which indicate that files were
+    /// lost or mixed up. Call it after `finish`.
+    pub fn verify(&self) -> Vec<String> {
+        let records = &self.records;
+        let live = &self.live;
+        let versions = &self.versions;
+        let mut problems: Vec<String> = self
+            .warnings
+            .iter()
+            .chain(&live.warnings)
+            .chain(&versions.warnings)
+            .cloned()
+            .collect();
+        let has =
+            |matches: fn(&VersionEdit) -> bool| records.iter().any(|r| r.edits.iter().any(matches));
+        let has_next_file_number = has(|e| matches!(e, VersionEdit::NextFileNumber(_)));
+        if !has_next_file_number {
+            problems.push("No NextFileNumber in manifest".to_string());
+        }
+        if !has(|e| matches!(e, VersionEdit::LogNumber(_))) {
+            problems.push("No LogNumber in manifest".to_string());
+        }
+        if !has(|e| matches!(e, VersionEdit::LastSequence(_))) {
+            problems.push("No LastSequence in manifest".to_string());
+        }
+        let next_file_number = versions.next_file_number;
+        let mut alive: Vec<u64> = live
+            .files
+            .values()
+            .filter(|f| !f.deleted)
+            .map(|f| f.file_number)
+            .chain(
+                live.blob_files
+                    .values()
+                    .filter(|b| !b.is_obsolete())
+                    .map(|b| b.addition.blob_file_number),
+            )
+            .collect();
+        alive.sort();
+        for file_number in alive {
+            if has_next_file_number && file_number >= next_file_number {
+                problems.push(format!(
+                    "File {} is not below NextFileNumber {}",
+                    file_number, next_file_number
+                ));
+            }
+        }
+        for cf in versions.live_column_families() {
+            for files in cf.levels.values() {
+                for meta in files.values() {
+                    if meta.smallest_seqno > meta.largest_seqno {
+                        problems.push(format!(
+                            "File {}: smallest_seqno {} above largest_seqno {}",
+                            meta.file_number, meta.smallest_seqno, meta.largest_seqno
+                        ));
+                    }
+                }
+            }
+        }
+        problems
+    }
+
+    fn apply(&mut self, index: usize) {
+        let edits = &self.records[index].edits;
+        self.live.apply(edits);
+        self.versions.apply(edits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::VersionEdit;
+
+    fn record(index: usize, group_remaining: Option<u32>) -> Record {
+        let mut edits = vec![VersionEdit::LogNumber(index as u64)];
+        edits.extend(group_remaining.map(VersionEdit::InAtomicGroup));
+        crate::reader::record(index, edits)
+    }
+
+    fn indices(replay: &Replay) -> Vec<usize> {
+        replay.records.iter().map(|r| r.index).collect()
+    }
+
+    #[test]
+    fn complete_group_is_kept() {
+        let mut replay = Replay::default();
+        replay.push(record(0, None));
+        replay.push(record(1, Some(1)));
+        let position = replay.push(record(2, Some(0))).unwrap();
+        replay.finish();
+        assert_eq!(
+            (position.group, position.position, position.size),
+            (1, 2, 2)
+        );
+        assert_eq!(indices(&replay), vec![0, 1, 2]);
+        assert!(replay.warnings.is_empty());
+    }
+
+    #[test]
+    fn group_is_applied_when_complete() {
+        let mut replay = Replay::default();
+        replay.push(record(0, None));
+        let applied =
+            |replay: &Replay| replay.applied().iter().map(|r| r.index).collect::<Vec<_>>();
+        assert_eq!(applied(&replay), vec![0]);
+        replay.push(record(1, Some(1)));
+        assert!(applied(&replay).is_empty());
+        replay.push(record(2, Some(0)));
+        assert_eq!(applied(&replay), vec![1, 2]);
+    }
+
+    #[test]
+    fn group_interrupted_by_plain_record_is_removed() {
+        let mut replay = Replay::default();
+        replay.push(record(0, Some(2)));
+        replay.push(record(1, Some(1)));
+        replay.push(record(2, None));
+        replay.finish();
+        assert_eq!(indices(&replay), vec![2]);
+        assert_eq!(replay.warnings.len(), 1);
+    }
+
+    #[test]
+    fn group_interrupted_by_new_group_is_removed() {
+        let mut replay = Replay::default();
+        replay.push(record(0, Some(2)));
+        replay.push(record(1, Some(1)));
+        let position = replay.push(record(2, Some(1))).unwrap();
+        replay.push(record(3, Some(0)));
+        replay.finish();
+        assert_eq!(
+            (position.group, position.position, position.size),
+            (2, 1, 2)
+        );
+        assert_eq!(indices(&replay), vec![2, 3]);
+        assert_eq!(replay.warnings.len(), 1);
+    }
+
+    #[test]
+    fn incomplete_trailing_group_is_removed() {
+        let mut replay = Replay::default();
+        replay.push(record(0, None));
+        replay.push(record(1, Some(1)));
+        replay.finish();
+        assert_eq!(indices(&replay), vec![0]);
+        assert_eq!(replay.warnings.len(), 1);
+    }
+
+    #[test]
+    fn verify_reports_missing_bookkeeping() {
+        let mut replay = Replay::default();
+        replay.push(record(0, None));
+        replay.finish();
+        assert_eq!(
+            replay.verify(),
+            vec![
+                "No NextFileNumber in manifest",
+                "No LastSequence in manifest"
+            ]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Bytes flushed, read and written by compactions, and the resulting
+//! write amplification, computed from the sizes of added and deleted
+//! files.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::compaction::{ClassifiedRecord, RecordKind};
+use crate::edit::FileMetaData;
+
+/// Compaction traffic into one output level, like the rows of RocksDB's
+/// compaction stats.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LevelStats {
+    /// Number of compactions into the level.
+    pub compactions: usize,
+    /// Rn: inputs from the levels above.
+    pub read_from_previous: u64,
+    /// Rn+1: inputs from the output level.
+    pub read_from_level: u64,
+    /// Compaction outputs, flushes for L0.
+    pub written: u64,
+    /// Trivially moved here, not rewritten.
+    pub moved_in: u64,
+}
+
+impl LevelStats {
+    /// Bytes written per byte read from the levels above, like RocksDB's
+    /// W-Amp column.
+    pub fn write_amplification(&self) -> Option<f64> {
+        ratio(self.written, self.read_from_previous)
+    }
+
+    fn add(&mut self, other: &LevelStats) {
+        self.compactions += other.compactions;
+        self.read_from_previous += other.read_from_previous;
+        self.read_from_level += other.read_from_level;
+        self.written += other.written;
+        self.moved_in += other.moved_in;
+    }
+}
+
+/// The statistics of one column family.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnFamilyStats {
+    /// Number of flushes.
+    pub flushes: usize,
+    /// Bytes written by flushes.
+    pub flushed: u64,
+    /// Bytes of ingested files.
+    pub ingested: u64,
+    /// Compaction traffic by output level.
+    pub levels: BTreeMap<u32, LevelStats>,
+}
+
+impl ColumnFamilyStats {
+    /// All levels added up.
+    pub fn sum(&self) -> LevelStats {
+        let mut sum = LevelStats::default();
+        for level in self.levels.values() {
+            sum.add(level);
+        }
+        sum
+    }
+
+    /// Bytes written by flushes and compactions per byte flushed.
+    pub fn write_amplification(&self) -> Option<f64> {
+        ratio(self.sum().written, self.flushed)
+    }
+}
+
+/// Bytes flushed and compacted in one time window.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WindowStats {
+    /// Bytes written by flushes.
+    pub flushed: u64,
+    /// Bytes of compaction inputs.
+    pub compaction_read: u64,
+    /// Bytes of compaction outputs.
+    pub compaction_written: u64,
+}
+
+impl WindowStats {
+    /// Bytes written by flushes and compactions per byte flushed.
+    pub fn write_amplification(&self) -> Option<f64> {
+        ratio(self.flushed + self.compaction_written, self.flushed)
+    }
+}
+
+fn ratio(a: u64, b: u64) -> Option<f64> {
+    (b != 0).then(|| a as f64 / b as f64)
+}
+
+/// Write statistics of a whole manifest, per column family and per time
+/// window. Records are put into windows by the `file_creation_time` of
+/// the files they wrote, those without one into the `None` window.
+#[derive(Debug, Clone, Default)]
+pub struct WriteStats {
+    /// Statistics by column family id.
+    pub column_families: BTreeMap<u32, ColumnFamilyStats>,
+    /// Window length in seconds, not 0.
+    pub window: Option<u64>,
+    /// Stats by window start, `None` for records without a time.
+    pub windows: BTreeMap<Option<u64>, WindowStats>,
+}
+
+impl WriteStats {
+    /// Adds up the flushes, ingestions, compactions and trivial moves of
+    /// the records. Sizes of deleted files are taken from the records
+    /// which added them, files added before the manifest started are
+    /// only known from its snapshot.
+    pub fn build(records: &[ClassifiedRecord], window: Option<u64>) -> WriteStats {
+        let window = window.filter(|length| *length > 0);
+        let mut stats = WriteStats {
+            window,
+            ..Default::default()
+        };
+        let mut sizes: HashMap<u64, u64> = HashMap::new();
+        for record in records {
+            let outputs: Vec<&FileMetaData> = record.output_files().collect();
+            let written: u64 = outputs.iter().map(|meta| meta.file_size).sum();
+            let cf = stats
+                .column_families
+                .entry(record.column_family)
+                .or_default();
+            let mut window_stats = WindowStats::default();
+            match record.kind {
+                RecordKind::Flush => {
+                    cf.flushes += 1;
+                    cf.flushed += written;
+                    for meta in &outputs {
+                        cf.levels.entry(meta.level).or_default().written += meta.file_size;
+                    }
+                    window_stats.flushed = written;
+                }
+                RecordKind::Ingestion => cf.ingested += written,
+                RecordKind::Compaction => {
+                    let output_level = outputs.iter().map(|meta| meta.level).max().unwrap_or(0);
+                    let level = cf.levels.entry(output_level).or_default();
+                    level.compactions += 1;
+                    level.written += written;
+                    for (input_level, file_number) in record.input_files() {
+                        let size = sizes.get(file_number).copied().unwrap_or(0);
+                        if *input_level == output_level {
+                            level.read_from_level += size;
+                        } else {
+                            level.read_from_previous += size;
+                        }
+                        window_stats.compaction_read += size;
+                    }
+                    window_stats.compaction_written = written;
+                }
+                _ => {}
+            }
+            for (file_number, _, to) in &record.moved_files {
+                let size = sizes.get(file_number).copied().unwrap_or(0);
+                cf.levels.entry(*to).or_default().moved_in += size;
+            }
+            for meta in &record.new_files {
+                sizes.insert(meta.file_number, meta.file_size);
+            }
+            let Some(length) = window else {
+                continue;
+            };
+            if matches!(record.kind, RecordKind::Flush | RecordKind::Compaction) {
+                let start = outputs
+                    .iter()
+                    .map(|meta| meta.file_creation_time)
+                    .filter(|t| *t != 0)
+                    .max()
+                    .map(|t| t - t % length);
+                let totals = stats.windows.entry(start).or_default();
+                totals.flushed += window_stats.flushed;
+                totals.compaction_read += window_stats.compaction_read;
+                totals.compaction_written += window_stats.compaction_written;
+            }
+        }
+        stats
+            .column_families
+            .retain(|_, cf| cf.flushes > 0 || cf.ingested > 0 || !cf.levels.is_empty());
+        stats
+    }
+}
@@ -0,0 +1,517 @@
+//! Replaying edits into the state RocksDB would recover from a manifest.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::edit::{record_column_family, BlobFileMetaData, FileMetaData, UnixTime, VersionEdit};
+use crate::reader::Record;
+
+/// The files of one column family, as RocksDB's Version would hold them.
+#[derive(Debug, Clone)]
+pub struct ColumnFamilyVersion {
+    /// Column family id.
+    pub id: u32,
+    /// Column family name.
+    pub name: String,
+    /// Name of the comparator, if the manifest told it.
+    pub comparator: Option<String>,
+    /// WALs below this number are not needed by the column family.
+    pub log_number: u64,
+    /// Dropped by a ColumnFamilyDrop.
+    pub dropped: bool,
+    /// Files by level and file number.
+    pub levels: BTreeMap<u32, BTreeMap<u64, FileMetaData>>,
+}
+
+impl ColumnFamilyVersion {
+    fn new(id: u32, name: &str) -> Self {
+        ColumnFamilyVersion {
+            id,
+            name: name.to_string(),
+            comparator: None,
+            log_number: 0,
+            dropped: false,
+            levels: BTreeMap::new(),
+        }
+    }
+
+    /// Number of files on all levels.
+    pub fn file_count(&self) -> usize {
+        self.levels.values().map(|files| files.len()).sum()
+    }
+
+    /// Bytes of the files on all levels.
+    pub fn total_size(&self) -> u64 {
+        self.levels
+            .values()
+            .flat_map(|files| files.values())
+            .map(|f| f.file_size)
+            .sum()
+    }
+
+    /// Smallest and largest user key of a set of files. Keys are compared
+    /// bytewise (reversed for the reverse bytewise comparator), which is
+    /// only an approximation for other custom comparators.
+    pub fn key_range<'a, I>(&self, files: I) -> Option<(&'a [u8], &'a [u8])>
+    where
+        I: IntoIterator<Item = &'a FileMetaData>,
+    {
+        let reverse = self.comparator.as_deref() == Some("rocksdb.ReverseBytewiseComparator");
+        let mut range: Option<(&[u8], &[u8])> = None;
+        for file in files {
+            let smallest = file.smallest_key.user_key.as_slice();
+            let largest = file.largest_key.user_key.as_slice();
+            range = Some(match range {
+                None => (smallest, largest),
+                Some((lo, hi)) => {
+                    if reverse {
+                        (lo.max(smallest), hi.min(largest))
+                    } else {
+                        (lo.min(smallest), hi.max(largest))
+                    }
+                }
+            });
+        }
+        range
+    }
+
+    /// Aggregates of a set of files, usually those of one level.
+    pub fn shape<'a, I>(&self, files: I) -> LevelShape<'a>
+    where
+        I: IntoIterator<Item = &'a FileMetaData>,
+    {
+        let files: Vec<&FileMetaData> = files.into_iter().collect();
+        LevelShape {
+            files: files.len(),
+            size: files.iter().map(|f| f.file_size).sum(),
+            key_range: self.key_range(files.iter().copied()),
+            min_seqno: files.iter().map(|f| f.smallest_seqno).min(),
+            max_seqno: files.iter().map(|f| f.largest_seqno).max(),
+            oldest_ancester_time: files
+                .iter()
+                .map(|f| f.oldest_ancester_time)
+                .filter(|t| *t != 0)
+                .min(),
+            needs_compaction: files.iter().filter(|f| f.needs_compaction).count(),
+            compensated_range_deletion_size: files
+                .iter()
+                .map(|f| f.compensated_range_deletion_size)
+                .sum(),
+        }
+    }
+}
+
+/// Aggregates of a set of files, e.g. of one level, like a row of RocksDB's
+/// "Level Files Size" stats.
+#[derive(Debug, Clone, Default)]
+pub struct LevelShape<'a> {
+    /// Number of files.
+    pub files: usize,
+    /// Bytes of the files.
+    pub size: u64,
+    /// Smallest and largest user key.
+    pub key_range: Option<(&'a [u8], &'a [u8])>,
+    /// Smallest sequence number.
+    pub min_seqno: Option<u64>,
+    /// Largest sequence number.
+    pub max_seqno: Option<u64>,
+    /// Unix seconds, unset times ignored.
+    pub oldest_ancester_time: Option<u64>,
+    /// Number of files marked for compaction.
+    pub needs_compaction: usize,
+    /// Sum of the compensated range deletion sizes.
+    pub compensated_range_deletion_size: u64,
+}
+
+/// Replays version edits into per column family versions, like RocksDB's
+/// VersionSet does during recovery.
+#[derive(Debug, Clone)]
+pub struct VersionSet {
+    /// Column families by id, dropped ones included.
+    pub column_families: BTreeMap<u32, ColumnFamilyVersion>,
+    /// Next number to use for a file.
+    pub next_file_number: u64,
+    /// Last sequence number used.
+    pub last_sequence: u64,
+    /// Log number of the previous manifest, only in old manifests.
+    pub prev_log_number: u64,
+    /// WALs below this number are not needed by any column family.
+    pub min_log_number_to_keep: u64,
+    /// Largest column family id used so far.
+    pub max_column_family: u32,
+    /// Problems found while replaying, not yet reported.
+    pub warnings: Vec<String>,
+}
+
+impl Default for VersionSet {
+    fn default() -> Self {
+        let mut column_families = BTreeMap::new();
+        // The default column family exists without being added
+        column_families.insert(0, ColumnFamilyVersion::new(0, "default"));
+        VersionSet {
+            column_families,
+            next_file_number: 0,
+            last_sequence: 0,
+            prev_log_number: 0,
+            min_log_number_to_keep: 0,
+            max_column_family: 0,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl VersionSet {
+    /// Applies the edits of one record to the column family it refers to.
+    pub fn apply(&mut self, edits: &[VersionEdit]) {
+        let id = record_column_family(edits);
+        for e in edits {
+            if let VersionEdit::ColumnFamilyAdd(name) = e {
+                if self.column_families.get(&id).is_some_and(|cf| !cf.dropped) {
+                    self.warnings
+                        .push(format!("Column family {} added twice", id));
+                }
+                self.column_families
+                    .insert(id, ColumnFamilyVersion::new(id, name));
+            }
+        }
+        let Some(cf) = self.column_families.get_mut(&id) else {
+            self.warnings
+                .push(format!("Edits for unknown column family {}", id));
+            return;
+        };
+        // Like RocksDB's VersionBuilder, deletions are applied before
+        // additions, so that a trivial move never loses its file.
+        for e in edits {
+            if let VersionEdit::DeletedFile(level, file_number) = e {
+                let removed = cf
+                    .levels
+                    .get_mut(level)
+                    .and_then(|files| files.remove(file_number));
+                if removed.is_none() {
+                    self.warnings.push(format!(
+                        "Column family {}: file {} not found on level {} for deletion",
+                        id, file_number, level
+                    ));
+                }
+            }
+        }
+        for e in edits {
+            match e {
+                VersionEdit::Comparator(name) => cf.comparator = Some(name.clone()),
+                VersionEdit::LogNumber(num) => cf.log_number = *num,
+                VersionEdit::NextFileNumber(num) => self.next_file_number = *num,
+                VersionEdit::LastSequence(seq) => self.last_sequence = *seq,
+                VersionEdit::PrevLogNumber(num) => self.prev_log_number = *num,
+                VersionEdit::MinLogNumberToKeep(num) => self.min_log_number_to_keep = *num,
+                VersionEdit::MaxColumnFamily(num) => self.max_column_family = *num,
+                VersionEdit::ColumnFamilyDrop => cf.dropped = true,
+                VersionEdit::NewFile4(meta) => {
+                    cf.levels
+                        .entry(meta.level)
+                        .or_default()
+                        .insert(meta.file_number, meta.clone());
+                }
+                _ => {}
+            }
+        }
+        cf.levels.retain(|_, files| !files.is_empty());
+    }
+
+    /// Column families which have not been dropped.
+    pub fn live_column_families(&self) -> impl Iterator<Item = &ColumnFamilyVersion> {
+        self.column_families.values().filter(|cf| !cf.dropped)
+    }
+}
+
+/// Column family names by id, as told by the manifest so far, including
+/// those of dropped column families. Unlike the names in [`VersionSet`],
+/// these only need the records and not the files, so the classifier can
+/// follow them record by record, and they keep where a column family was
+/// dropped.
+#[derive(Debug, Clone)]
+pub struct ColumnFamilyNames {
+    /// Names by column family id.
+    pub names: BTreeMap<u32, String>,
+    /// Index and offset of the dropping record by column family id.
+    pub dropped: BTreeMap<u32, (usize, u64)>,
+}
+
+impl Default for ColumnFamilyNames {
+    fn default() -> Self {
+        // The default column family exists without being added
+        ColumnFamilyNames {
+            names: BTreeMap::from([(0, "default".to_string())]),
+            dropped: BTreeMap::new(),
+        }
+    }
+}
+
+impl ColumnFamilyNames {
+    /// Takes the additions and drops from a record.
+    pub fn apply(&mut self, record: &Record) {
+        let id = record_column_family(&record.edits);
+        for e in &record.edits {
+            match e {
+                VersionEdit::ColumnFamilyAdd(name) => {
+                    self.names.insert(id, name.clone());
+                    self.dropped.remove(&id);
+                }
+                VersionEdit::ColumnFamilyDrop => {
+                    self.dropped.insert(id, (record.index, record.offset));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Name of the column family, if known.
+    pub fn name(&self, id: u32) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+
+    /// The id with the name, if known, for printing.
+    pub fn label(&self, id: u32) -> ColumnFamilyLabel<'_> {
+        ColumnFamilyLabel {
+            id,
+            name: self.name(id),
+        }
+    }
+}
+
+/// Prints a column family as `10 (ten)`, or just `10` without a name.
+#[derive(Debug, Clone, Copy)]
+pub struct ColumnFamilyLabel<'a> {
+    /// Column family id.
+    pub id: u32,
+    /// Column family name, if known.
+    pub name: Option<&'a str>,
+}
+
+impl fmt::Display for ColumnFamilyLabel<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{} ({})", self.id, name),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// Files and blob files seen so far while replaying the manifest.
+#[derive(Default)]
+pub struct LiveFiles {
+    /// Every file added, deleted ones included.
+    pub files: HashMap<u64, FileMetaData>,
+    /// Every blob file added, obsolete ones included.
+    pub blob_files: HashMap<u64, BlobFileMetaData>,
+    /// Bytes of the live files.
+    pub total_size: u64,
+    /// Bytes of the blobs in live blob files.
+    pub total_blob_size: u64,
+    /// Last synced size by log number.
+    pub wals: BTreeMap<u64, Option<u64>>,
+    /// Levels every file lived on, by file number.
+    pub level_history: HashMap<u64, Vec<u32>>,
+    /// Files and bytes moved, by old and new level.
+    pub trivial_moves: BTreeMap<(u32, u32), (usize, u64)>,
+    /// Not yet reported.
+    pub warnings: Vec<String>,
+}
+
+impl LiveFiles {
+    /// Number of files and bytes moved to another level without being
+    /// rewritten.
+    pub fn moved(&self) -> (usize, u64) {
+        self.trivial_moves
+            .values()
+            .fold((0, 0), |(files, bytes), (f, b)| (files + f, bytes + b))
+    }
+
+    /// Applies the edits of one record. A file deleted from one level and
+    /// added to another in the same record is a trivial move: it keeps
+    /// its data and only changes level. Dropping a column family deletes
+    /// all its files without DeletedFile edits.
+    pub fn apply(&mut self, edits: &[VersionEdit]) {
+        let added: HashMap<u64, u32> = edits
+            .iter()
+            .filter_map(|e| match e {
+                VersionEdit::NewFile4(meta) => Some((meta.file_number, meta.level)),
+                _ => None,
+            })
+            .collect();
+        // Deletions first, like RocksDB's VersionBuilder
+        for e in edits {
+            if let VersionEdit::DeletedFile(level, file_number) = e {
+                match self.files.get_mut(file_number) {
+                    Some(meta) if !meta.deleted => {
+                        meta.deleted = true;
+                        self.total_size -= meta.file_size;
+                        if let Some(&to) = added.get(file_number).filter(|to| **to != *level) {
+                            let moves = self.trivial_moves.entry((*level, to)).or_default();
+                            moves.0 += 1;
+                            moves.1 += meta.file_size;
+                        }
+                    }
+                    Some(_) => {
+                        self.warnings
+                            .push(format!("File {} deleted twice", file_number));
+                    }
+                    None => {
+                        self.warnings
+                            .push(format!("File {} not found for deletion", file_number));
+                    }
+                }
+            }
+        }
+        for e in edits {
+            match e {
+                VersionEdit::NewFile4(meta) => {
+                    let replaced = self.files.insert(meta.file_number, meta.clone());
+                    if let Some(old) = replaced.filter(|old| !old.deleted) {
+                        self.warnings
+                            .push(format!("File {} added twice", meta.file_number));
+                        self.total_size -= old.file_size;
+                    }
+                    self.total_size += meta.file_size;
+                    let levels = self.level_history.entry(meta.file_number).or_default();
+                    if levels.last() != Some(&meta.level) {
+                        levels.push(meta.level);
+                    }
+                }
+                VersionEdit::BlobFileAddition(addition) => {
+                    self.blob_files.insert(
+                        addition.blob_file_number,
+                        BlobFileMetaData {
+                            addition: addition.clone(),
+                            column_family: record_column_family(edits),
+                            garbage_blob_count: 0,
+                            garbage_blob_bytes: 0,
+                            dropped_with_column_family: false,
+                        },
+                    );
+                    self.total_blob_size += addition.total_blob_bytes;
+                }
+                VersionEdit::BlobFileGarbage(garbage) => {
+                    match self.blob_files.get_mut(&garbage.blob_file_number) {
+                        Some(meta) => {
+                            let was_obsolete = meta.is_obsolete();
+                            meta.garbage_blob_count += garbage.garbage_blob_count;
+                            meta.garbage_blob_bytes += garbage.garbage_blob_bytes;
+                            if !was_obsolete && meta.is_obsolete() {
+                                self.total_blob_size -= meta.addition.total_blob_bytes;
+                            }
+                        }
+                        None => {
+                            self.warnings.push(format!(
+                                "Blob file {} not found for garbage",
+                                garbage.blob_file_number
+                            ));
+                        }
+                    }
+                }
+                VersionEdit::WalAddition(log_number, synced_size) => {
+                    let entry = self.wals.entry(*log_number).or_insert(None);
+                    if synced_size.is_some() {
+                        *entry = *synced_size;
+                    }
+                }
+                VersionEdit::WalDeletion(log_number) => {
+                    self.wals = self.wals.split_off(log_number);
+                }
+                VersionEdit::ColumnFamilyDrop => {
+                    let id = record_column_family(edits);
+                    for meta in self.files.values_mut() {
+                        if meta.column_family == id && !meta.deleted {
+                            meta.deleted = true;
+                            meta.dropped_with_column_family = true;
+                            self.total_size -= meta.file_size;
+                        }
+                    }
+                    for meta in self.blob_files.values_mut() {
+                        if meta.column_family == id && !meta.is_obsolete() {
+                            meta.dropped_with_column_family = true;
+                            self.total_blob_size -= meta.addition.total_blob_bytes;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Where to stop replaying the manifest, to see the LSM tree as it was at
+/// some point in time.
+#[derive(Debug, Clone, Copy)]
+pub enum ReplayLimit {
+    /// Last record (counted from 0) to apply.
+    Record(usize),
+    /// Last record start offset to apply.
+    Offset(u64),
+    /// Last sequence number to include.
+    Sequence(u64),
+    /// Latest file creation time to include, in unix seconds.
+    Time(u64),
+}
+
+impl ReplayLimit {
+    /// Tells if the record lies beyond the limit and must not be applied
+    /// any more.
+    pub fn stops_before(&self, record: &Record) -> bool {
+        match self {
+            ReplayLimit::Record(last) => record.index > *last,
+            ReplayLimit::Offset(last) => record.offset > *last,
+            ReplayLimit::Sequence(last) => record
+                .edits
+                .iter()
+                .any(|e| matches!(e, VersionEdit::LastSequence(seq) if seq > last)),
+            ReplayLimit::Time(last) => record.edits.iter().any(
+                |e| matches!(e, VersionEdit::NewFile4(meta) if meta.file_creation_time > *last),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ReplayLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayLimit::Record(last) => write!(f, "record #{}", last),
+            ReplayLimit::Offset(last) => write!(f, "offset {:x}", last),
+            ReplayLimit::Sequence(last) => write!(f, "sequence number {}", last),
+            ReplayLimit::Time(last) => write!(f, "file creation time {}", UnixTime(*last)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::BlobFileAddition;
+
+    fn blob(blob_file_number: u64, total_blob_bytes: u64) -> VersionEdit {
+        VersionEdit::BlobFileAddition(BlobFileAddition {
+            blob_file_number,
+            total_blob_count: 1,
+            total_blob_bytes,
+            checksum_method: String::new(),
+            checksum_value: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn dropping_a_column_family_makes_its_blob_files_obsolete() {
+        let mut live = LiveFiles::default();
+        live.apply(&[blob(5, 100)]);
+        live.apply(&[VersionEdit::ColumnFamily(2), blob(6, 200)]);
+        assert_eq!(live.total_blob_size, 300);
+        live.apply(&[VersionEdit::ColumnFamily(2), VersionEdit::ColumnFamilyDrop]);
+        assert_eq!(live.total_blob_size, 100);
+        assert!(!live.blob_files[&5].is_obsolete());
+        assert!(live.blob_files[&6].is_obsolete());
+        assert!(live.blob_files[&6].dropped_with_column_family);
+        let expected: Vec<u64> = crate::directory::expected_files(&live)
+            .iter()
+            .map(|f| f.number)
+            .collect();
+        assert_eq!(expected, vec![5]);
+    }
+}